@@ -1,21 +1,30 @@
 use cosmwasm_std::{
     entry_point, from_binary, to_binary, Env, Deps, DepsMut,
-    MessageInfo, Response, StdError, StdResult, Addr, CanonicalAddr,
+    MessageInfo, Response, StdError, StdResult, Storage, Api, Addr, CanonicalAddr,
     Binary, CosmosMsg, Uint128
 };
 use crate::error::ContractError;
-use crate::msg::{ HandleReceiveMsg, ExecuteMsg, ReceiveMsg, PackBuildMsg, PackTransferMsg, InstantiateMsg, QueryMsg, HistoryToken, PackMain, PackMember, BuildInfoResponse, PaymentContractInfo };
-use crate::state::{ State, CONFIG_ITEM, LEVEL_ITEM, PAID_ADDRESSES_ITEM, RANK_STORE, INHOLDING_NFT_STORE, PACK_MAIN_STORE, PACK_MEMBER_STORE, ADMIN_ITEM, MY_ADDRESS_ITEM, PREFIX_REVOKED_PERMITS, HISTORY_STORE};
+use crate::msg::{ HandleReceiveMsg, ExecuteMsg, ReceiveMsg, PackBuildMsg, PackTransferMsg, PackDisbandMsg, PackMergeMsg, PackRenameMsg, InstantiateMsg, MigrateMsg, QueryMsg, HistoryToken, HistoryKind, PackMain, PackMember, BuildInfoResponse, PackCapacityResponse, PackBuildPreviewResponse, ViewingKeyResponse, PaymentContractInfo, ContractInfo, BuildCredit, CreditKind, Level, Rank, MemberRequirement, TraitNames, PackRankRule, RankScore, LeaderboardOrder, LeaderboardEntry, PackPositionResponse };
+use crate::state::{ State, CONFIG_ITEM, LEVEL_ITEM, BUILD_CREDIT_STORE, RANK_STORE, INHOLDING_NFT_STORE, PACK_MAIN_STORE, PACK_MEMBER_STORE, PACK_RANK_SCORES_ITEM, PACK_RANK_INDEX_STORE, PACK_COUNT_SCORES_ITEM, PACK_COUNT_INDEX_STORE, NAME_BLOCKLIST_ITEM, PACK_NAME_STORE, MEMBER_PACK_STORE, ADMIN_ITEM, PREFIX_REVOKED_PERMITS, HISTORY_STORE, LEGACY_HISTORY_STORE, LEGACY_CONFIG_ITEM, LEGACY_PAID_ADDRESSES_ITEM};
 use crate::rand::{sha_256};
+use std::str::FromStr;
 use secret_toolkit::{
+    storage::{ Item, Keymap },
     snip20::{ transfer_msg },
     snip721::{
-        batch_transfer_nft_msg, batch_burn_nft_msg, register_receive_nft_msg, set_viewing_key_msg, nft_dossier_query, transfer_nft_msg, set_metadata_msg, Transfer, Trait, ViewerInfo, MediaFile, Metadata, NftDossier, Burn
+        batch_transfer_nft_msg, batch_burn_nft_msg, register_receive_nft_msg, set_viewing_key_msg, nft_dossier_query, transfer_nft_msg, set_metadata_msg, Transfer, Trait, ViewerInfo, MediaFile, Metadata, Extension, NftDossier, Burn
     },
-    permit::{validate, Permit, RevokedPermits}
+    permit::{validate, Permit, RevokedPermits},
+    viewing_key::{ViewingKey, ViewingKeyStore}
 };  
 
 pub const BLOCK_SIZE: usize = 256;
+pub const MAX_RANK_BATCH: usize = 500;
+pub const DEFAULT_MEMBER_XP: u32 = 464;
+pub const MAX_LEADERBOARD_LIMIT: u32 = 100;
+pub const MIN_PACK_NAME_LENGTH: usize = 3;
+pub const MAX_PACK_NAME_LENGTH: usize = 32;
+pub const VIEWING_KEY_SEED_DOMAIN: &[u8] = b"pack_builder_viewing_key_seed";
 
 
 #[entry_point]
@@ -24,7 +33,7 @@ pub fn instantiate(
     _env: Env,
     info: MessageInfo,
     msg: InstantiateMsg
-) -> Result<Response, StdError> {
+) -> Result<Response, ContractError> {
     let prng_seed: Vec<u8> = sha_256(base64::encode(msg.entropy).as_bytes()).to_vec();
     let viewing_key = base64::encode(&prng_seed);
 
@@ -39,17 +48,27 @@ pub fn instantiate(
         pack_max: msg.pack_max,
         collection_size: msg.collection_size,
         level_cap: msg.level_cap,
-        is_payment_needed: msg.is_payment_needed
+        member_requirement: msg.member_requirement.unwrap_or(MemberRequirement::MinXp { xp: DEFAULT_MEMBER_XP }),
+        trait_names: msg.trait_names.unwrap_or_default(),
+        pack_rank_rule: msg.pack_rank_rule.unwrap_or_default(),
+        is_payment_needed: msg.is_payment_needed,
+        is_rename_payment_needed: false,
+        unique_pack_names: false
     }; 
 
     //Save Contract state
     CONFIG_ITEM.save(deps.storage, &state)?;
+    validate_levels(&msg.levels)?;
+    required_member_xp(&state, &msg.levels)?;
+    validate_trait_names(&state.trait_names)?;
+    validate_pack_rank_rule(&state.pack_rank_rule)?;
     LEVEL_ITEM.save(deps.storage, &msg.levels)?;
     ADMIN_ITEM.save(deps.storage, &deps.api.addr_canonicalize(&info.sender.to_string())?)?;
-    MY_ADDRESS_ITEM.save(deps.storage,  &deps.api.addr_canonicalize(&_env.contract.address.to_string())?)?;
-    PAID_ADDRESSES_ITEM.save(deps.storage, &Vec::new())?;
+    //the contract's own nft viewing key is the encoded prng seed, so user keys get a seed of their own
+    ViewingKey::set_seed(deps.storage, &viewing_key_seed(&prng_seed));
 
     for rank in msg.ranks.iter() {
+        validate_rank(&state, rank)?;
         RANK_STORE.insert(deps.storage, &rank.token_id, &rank.rank)?;
     }
  
@@ -92,6 +111,72 @@ pub fn instantiate(
     Ok(Response::new().add_messages(response_msgs)) 
 }
 
+// Upgrades an instance running the code from before build credits. Its config and paid
+// addresses are converted here, the pack indices are filled in afterwards with ReindexPacks
+#[entry_point]
+pub fn migrate(
+    deps: DepsMut,
+    _env: Env,
+    msg: MigrateMsg
+) -> Result<Response, ContractError> {
+    //the paid addresses were only ever saved by the old code
+    if let Some(paid_addresses) = LEGACY_PAID_ADDRESSES_ITEM.may_load(deps.storage)? {
+        let old = LEGACY_CONFIG_ITEM.load(deps.storage)?;
+        let state = State { 
+            viewing_key: old.viewing_key,
+            owner: old.owner,  
+            nft_contract: old.nft_contract, 
+            //renames were new, they cost the same as a build until the admin sets a price
+            valid_payments: old.valid_payments.map(|x| x.into_iter().map(|payment| PaymentContractInfo {
+                code_hash: payment.code_hash,
+                address: payment.address,
+                payment_needed: payment.payment_needed,
+                rename_price: payment.payment_needed,
+                name: payment.name
+            }).collect()), 
+            receiving_address: old.receiving_address,
+            total_burned: old.total_burned,
+            pack_max: old.pack_max,
+            collection_size: old.collection_size,
+            level_cap: old.level_cap,
+            member_requirement: MemberRequirement::MinXp { xp: DEFAULT_MEMBER_XP },
+            trait_names: TraitNames::default(),
+            pack_rank_rule: PackRankRule::default(),
+            is_payment_needed: old.is_payment_needed,
+            is_rename_payment_needed: false,
+            unique_pack_names: false
+        }; 
+        CONFIG_ITEM.save(deps.storage, &state)?;
+
+        //the old code sent payments on to the receiving address straight away and didn't
+        //record which token paid, so these credits have nothing in escrow to refund
+        for raw_address in paid_addresses.iter() {
+            let mut credits = BUILD_CREDIT_STORE.get(deps.storage, raw_address).unwrap_or_else(Vec::new);
+            credits.push(BuildCredit {
+                payment_contract: None,
+                price: Uint128::zero(),
+                quantity: 1,
+                kind: CreditKind::Build
+            });
+            BUILD_CREDIT_STORE.insert(deps.storage, raw_address, &credits)?;
+        }
+        LEGACY_PAID_ADDRESSES_ITEM.remove(deps.storage);
+
+        let prng_seed: Vec<u8> = sha_256(base64::encode(msg.entropy).as_bytes()).to_vec();
+        ViewingKey::set_seed(deps.storage, &viewing_key_seed(&prng_seed));
+    }
+
+    let migrated = migrate_history(deps.storage, deps.api, &msg.history_addresses)?;
+    Ok(Response::new().add_attribute("history_migrated", migrated.to_string()))
+}
+
+// Seed for user viewing keys, hashed apart from the prng seed it is taken from
+fn viewing_key_seed(
+    prng_seed: &[u8]
+) -> Vec<u8> {
+    sha_256(&[VIEWING_KEY_SEED_DOMAIN, prng_seed].concat()).to_vec()
+}
+
 #[entry_point]
 pub fn execute(
     deps: DepsMut,
@@ -103,9 +188,58 @@ pub fn execute(
         ExecuteMsg::RevokePermit { permit_name } => {
             try_revoke_permit(deps, &info.sender, &permit_name)
         },
+        ExecuteMsg::CreateViewingKey { entropy, .. } => {
+            try_create_viewing_key(deps, _env, &info, entropy)
+        },
+        ExecuteMsg::SetViewingKey { key, .. } => {
+            try_set_viewing_key(deps, &info.sender, key)
+        },
+        ExecuteMsg::UpdateConfig { 
+            receiving_address,
+            pack_max,
+            collection_size,
+            level_cap,
+            is_payment_needed,
+            nft_contract,
+            member_requirement,
+            trait_names,
+            pack_rank_rule,
+            is_rename_payment_needed,
+            unique_pack_names
+        } => try_update_config(deps, _env, &info.sender, receiving_address, pack_max, collection_size, level_cap, is_payment_needed, nft_contract, member_requirement, trait_names, pack_rank_rule, is_rename_payment_needed, unique_pack_names),
+        ExecuteMsg::SetNameBlocklist { words } => {
+            try_set_name_blocklist(deps, &info.sender, words)
+        },
+        ExecuteMsg::RecomputePackRanks { start_page, page_size } => {
+            try_recompute_pack_ranks(deps, &info.sender, start_page, page_size)
+        },
+        ExecuteMsg::SetLevels { levels } => {
+            try_set_levels(deps, &info.sender, levels)
+        },
+        ExecuteMsg::AddLevels { levels } => {
+            try_add_levels(deps, &info.sender, levels)
+        },
+        ExecuteMsg::RemoveLevels { from_level } => {
+            try_remove_levels(deps, &info.sender, from_level)
+        },
+        ExecuteMsg::SetRanks { ranks } => {
+            try_set_ranks(deps, &info.sender, ranks)
+        },
+        ExecuteMsg::RemoveRanks { token_ids } => {
+            try_remove_ranks(deps, &info.sender, token_ids)
+        },
+        ExecuteMsg::ReindexPacks { start_page, page_size } => {
+            try_reindex_packs(deps, &info.sender, start_page, page_size)
+        },
+        ExecuteMsg::MigrateHistory { addresses } => {
+            try_migrate_history(deps, &info.sender, addresses)
+        },
         ExecuteMsg::AddPayment { payment } => {
             try_add_payment(deps, &info.sender, payment)
         },
+        ExecuteMsg::UpdatePayment { payment_name, payment_needed, rename_price, code_hash } => {
+            try_update_payment(deps, &info.sender, payment_name, payment_needed, rename_price, code_hash)
+        },
         ExecuteMsg::RemovePayment { payment_name } => {
             try_remove_payment(deps, &info.sender, payment_name)
         },
@@ -118,9 +252,18 @@ pub fn execute(
             amount,
             msg
         } => receive(deps, _env, &info.sender, &sender, &from, amount, msg),
-        // ExecuteMsg::ClaimBack {} => {
-        //     try_claim_back(deps, _env, &info.sender)
-        // },
+        ExecuteMsg::ClaimBack { token_ids } => {
+            try_claim_back(deps, _env, &info.sender, token_ids)
+        },
+        ExecuteMsg::AdminClaimBack { owner, token_ids } => {
+            try_admin_claim_back(deps, _env, &info.sender, owner, token_ids)
+        },
+        ExecuteMsg::RefundCredits {} => {
+            try_refund_credits(deps, _env, &info.sender)
+        },
+        ExecuteMsg::AdminRefundCredits { address } => {
+            try_admin_refund_credits(deps, _env, &info.sender, address)
+        },
         ExecuteMsg::SendNftBack { token_id, owner } => {
             try_send_nft_back(deps, _env, &info.sender, token_id, owner)
         }
@@ -139,44 +282,49 @@ fn receive(
     deps.api.debug(&format!("Receive received"));
 
     let state = CONFIG_ITEM.load(deps.storage)?;
-    let payment_contract = state.valid_payments.as_ref().unwrap().iter().find(|&x| &x.address == info_sender);
+    let payment_contract = state.valid_payments.as_ref().and_then(|x| x.iter().find(|&x| &x.address == info_sender));
 
     if let Some(bin) = msg { 
-        let bytes = base64::decode(bin.to_base64()).unwrap();
-        let rmsg: ReceiveMsg = serde_json::from_slice(&bytes).unwrap();
-        if !payment_contract.is_some(){
-            return Err(ContractError::CustomError {val: info_sender.to_string() + &" Address is not correct snip contract".to_string()});  
-        }  
+        let rmsg: ReceiveMsg = from_binary(&bin)?;
+        let payment_contract = payment_contract
+            .ok_or_else(|| ContractError::UnknownPaymentContract { address: info_sender.to_string() })?;
 
-        if payment_contract.unwrap().payment_needed * Uint128::from(rmsg.quantity) != amount {
-            return Err(ContractError::CustomError {val: "You've sent the wrong amount".to_string()});  
+        if rmsg.quantity == 0 {
+            return Err(ContractError::InvalidQuantity {});  
         }
 
-        let sender_raw = deps.api.addr_canonicalize(&sender.to_string())?; 
-        let mut paid_addresses = PAID_ADDRESSES_ITEM.load(deps.storage)?;
-
-        if paid_addresses.iter().any(|x| x == &sender_raw){
-            return Err(ContractError::CustomError {val: sender.to_string() + &" Address is already building".to_string()});  
-        } 
-        
-        paid_addresses.push(sender_raw);
+        let price = match rmsg.kind {
+            CreditKind::Build => payment_contract.payment_needed,
+            CreditKind::Rename => payment_contract.rename_price
+        };
+        if price * Uint128::from(rmsg.quantity) != amount {
+            return Err(ContractError::WrongPaymentAmount { expected: price * Uint128::from(rmsg.quantity), received: amount });  
+        }
 
-        PAID_ADDRESSES_ITEM.save(deps.storage, &paid_addresses)?;
+        //add a credit for every build or rename paid for, the payment stays in escrow until it is used
+        let sender_raw = deps.api.addr_canonicalize(&sender.to_string())?; 
+        let mut credits = BUILD_CREDIT_STORE.get(deps.storage, &sender_raw).unwrap_or_else(Vec::new);
+        let credit = credits.iter_mut().find(|x| x.payment_contract.as_ref().map(|c| &c.address) == Some(&payment_contract.address) && x.price == price && x.kind == rmsg.kind);
+        if let Some(credit) = credit {
+            credit.quantity = credit.quantity + rmsg.quantity as u32;
+        }
+        else {
+            credits.push(BuildCredit{
+                payment_contract: Some(ContractInfo{
+                    code_hash: payment_contract.code_hash.to_string(),
+                    address: payment_contract.address.clone()
+                }),
+                price: price,
+                quantity: rmsg.quantity as u32,
+                kind: rmsg.kind
+            });
+        }
+        BUILD_CREDIT_STORE.insert(deps.storage, &sender_raw, &credits)?;
     }
     else{
-        return Err(ContractError::CustomError {val: "Invalid message received".to_string()});
+        return Err(ContractError::MissingReceiveMsg {});
     } 
-    Ok(Response::new()
-    .add_message(transfer_msg(
-        state.receiving_address.to_string(),
-        amount,
-        None,
-        None,
-        BLOCK_SIZE,
-        payment_contract.unwrap().code_hash.to_string(),
-        payment_contract.unwrap().address.to_string(),
-        )?)
-    )
+    Ok(Response::default())
 }
 
 fn try_batch_receive(
@@ -189,6 +337,9 @@ fn try_batch_receive(
 ) -> Result<Response, ContractError> { 
     deps.api.debug(&format!("Batch received"));
 
+    //a token listed twice would have its xp and rank counted twice
+    check_unique(&token_ids)?;
+
     if let Some(bin_msg) = msg {
         match from_binary(&bin_msg)? {
             HandleReceiveMsg::ReceivePackBuild{ pack_build } => join_pack(
@@ -199,17 +350,41 @@ fn try_batch_receive(
                 token_ids,
                 pack_build
             ),
-            // HandleReceiveMsg::ReceiveTransferBuild{ transfer_build } => transfer_pack(
-            //     _env,
-            //     deps,
-            //     sender,
-            //     from, 
-            //     token_ids,
-            //     transfer_build
-            // )
+            HandleReceiveMsg::ReceiveTransferBuild{ transfer_build } => transfer_pack(
+                _env,
+                deps,
+                sender,
+                from, 
+                token_ids,
+                transfer_build
+            ),
+            HandleReceiveMsg::ReceiveDisbandPack{ disband } => disband_pack(
+                _env,
+                deps,
+                sender,
+                from, 
+                token_ids,
+                disband
+            ),
+            HandleReceiveMsg::ReceiveMergePacks{ merge } => merge_packs(
+                _env,
+                deps,
+                sender,
+                from, 
+                token_ids,
+                merge
+            ),
+            HandleReceiveMsg::ReceiveRenamePack{ rename } => rename_pack(
+                _env,
+                deps,
+                sender,
+                from, 
+                token_ids,
+                rename
+            )
         }
     } else {
-        return Err(ContractError::CustomError {val: "data should be given".to_string()});
+        return Err(ContractError::MissingReceiveMsg {});
     }
 }
 
@@ -225,248 +400,98 @@ pub fn join_pack(
     let mut response_msgs: Vec<CosmosMsg> = Vec::new();
     let mut response_attrs = vec![];
     let mut state = CONFIG_ITEM.load(deps.storage)?;   
-    let levels = LEVEL_ITEM.load(deps.storage)?;   
-    let mut paid_addresses = PAID_ADDRESSES_ITEM.load(deps.storage)?;
+
+    if sender != &state.nft_contract.address{
+        return Err(ContractError::InvalidNftContract { address: sender.to_string() });
+    }
 
     let raw_address = &deps.api.addr_canonicalize(&from.to_string())?;
-    let position = paid_addresses.iter().position(|x| x == raw_address);
-    
+    //builds keep taking any name as they always have, the naming rules only apply to renames
+    let name = pmsg.name.to_string();
 
-    // Check is payment is needed and if it is check if payment was received
-    if state.is_payment_needed && position.is_none(){
-        return Err(ContractError::CustomError {val: "Payment not received".to_string()});  
-    }
-    else{ 
-        paid_addresses.remove(position.unwrap());
+    // Check is payment is needed and if it is use up one of the build credits
+    if state.is_payment_needed {
+        response_msgs.extend(pay_with_build_credit(deps.storage, &state, raw_address, CreditKind::Build)?);
     }
 
-    let mut pack_members = PACK_MEMBER_STORE.get(deps.storage, &pmsg.main_token_id).unwrap_or_else(Vec::new);
-
     //Check to make sure main_token_id exists in list and remove from the list
     let pos = token_ids_mut.iter().position(|x| x == &pmsg.main_token_id);
     if pos.is_none(){
-        return Err(ContractError::CustomError {val: "Main Token is not in the list".to_string()});  
+        return Err(ContractError::MainTokenNotInList { token_id: pmsg.main_token_id.to_string() });  
     }
     else{
         token_ids_mut.remove(pos.unwrap());
     }
-    
-     
-     if sender == &state.nft_contract.address{ 
-
-        let history_store = HISTORY_STORE.add_suffix(from.to_string().as_bytes());
-       
-        // Get viewing key for NFTs
-        let viewer = Some(ViewerInfo {
-            address: _env.contract.address.to_string(),
-            viewing_key: state.viewing_key.as_ref().unwrap().to_string(),
-        });
- 
-        let mut public_media_to_add: Vec<MediaFile> = Vec::new();
-        let mut private_media_to_add: Vec<MediaFile> = Vec::new();
-        let mut xp_total: u32 = 0;
-        let mut pack_rank_total: u32 = 0;
-
-        for token_id in token_ids_mut.iter() { 
-            let rank: u16 = RANK_STORE.get(deps.storage, &token_id)
-            .ok_or_else(|| StdError::generic_err("Rank pool doesn't have token"))?;
-
-            let wolf_meta: NftDossier =  nft_dossier_query(
-                deps.querier,
-                token_id.to_string(),
-                viewer.clone(),
-                None,
-                BLOCK_SIZE,
-                state.nft_contract.code_hash.clone(),
-                state.nft_contract.address.to_string(),
-            )?;
-            let alpha_trait = wolf_meta.public_metadata.as_ref().unwrap().extension.as_ref().unwrap().attributes.as_ref().unwrap().iter().find(|&x| x.trait_type == Some("Alpha".to_string()));
-            if alpha_trait.is_some(){
-                return Err(ContractError::CustomError {val: "You can't combine two Alphas".to_string()});  
-            } 
-            let pub_attributes = wolf_meta.public_metadata.as_ref().unwrap().extension.as_ref().unwrap().attributes.as_ref().unwrap().clone();
-            let current_xp_trait = wolf_meta.public_metadata.as_ref().unwrap().extension.as_ref().unwrap().attributes.as_ref().unwrap().iter().find(|&x| x.trait_type == Some("XP".to_string())).unwrap();
-            xp_total = xp_total + current_xp_trait.value.parse::<u32>().unwrap();
-            pack_rank_total = pack_rank_total + (state.collection_size - rank) as u32;
-            //check that lvl/xp is high enough to be added to the pack
-            if current_xp_trait.value.parse::<u32>().unwrap() < 464{
-                return Err(ContractError::CustomError {val: "Wolf's level is not high enough".to_string()});  
-            }
-            public_media_to_add.push(wolf_meta.public_metadata.unwrap().extension.unwrap().media.unwrap().first().unwrap().clone());
-            private_media_to_add.push(wolf_meta.private_metadata.unwrap().extension.unwrap().media.unwrap().first().unwrap().clone());
-            
-            pack_members.push(PackMember{
-                token_id: token_id.to_string(),
-                rank:  rank,
-                attributes: pub_attributes
-            });
-            PACK_MEMBER_STORE.insert(deps.storage, &pmsg.main_token_id, &pack_members)?;
+    //an alpha sent on its own would only take the new name, which is what a rename is for
+    if token_ids_mut.is_empty() {
+        return Err(ContractError::EmptyTokenList {});
+    }
+
+    let plan = plan_pack_build(deps.as_ref(), &_env, &state, &pmsg.main_token_id, &token_ids_mut, Some(&name), Some(&_env.contract.address))?;
+    if plan.level > plan.previous_level {
+        response_attrs.push(("lvl_increase".to_string(), plan.level.to_string()));
+    }
+
+    save_pack_members(deps.storage, &pmsg.main_token_id, &plan.pack_members)?;
+
+    //update store for the leaderboard
+    save_pack_main(deps.storage, &state, &PackMain{
+        token_id: pmsg.main_token_id.to_string(),
+        pack_rank:  plan.pack_rank,
+        pack_count: plan.pack_size,
+        name: name
+    })?;
+
+    //Burn nfts that are not the main token
+    let mut burns: Vec<Burn> = Vec::new(); 
+    burns.push(
+        Burn{ 
+            token_ids: token_ids_mut.clone(),
+            memo: None
         }
-        //Burn nfts that are not the main token
-        let mut burns: Vec<Burn> = Vec::new(); 
-        burns.push(
-            Burn{ 
-                token_ids: token_ids_mut.clone(),
-                memo: None
-            }
-        );
+    );
 
-        let cosmos_batch_msg = batch_burn_nft_msg(
-            burns,
-            None,
-            BLOCK_SIZE,
-            state.nft_contract.code_hash.clone(),
-            state.nft_contract.address.to_string(),
-        )?;
-        response_msgs.push(cosmos_batch_msg);
+    let cosmos_batch_msg = batch_burn_nft_msg(
+        burns,
+        None,
+        BLOCK_SIZE,
+        state.nft_contract.code_hash.clone(),
+        state.nft_contract.address.to_string(),
+    )?;
+    response_msgs.push(cosmos_batch_msg);
 
-        // ------------------------------------------
-        //        Master NFT IMAGE AND XP UPDATE
-        // ------------------------------------------
-        let group_master_meta: NftDossier =  nft_dossier_query(
-            deps.querier,
-            pmsg.main_token_id.to_string(),
-            viewer.clone(),
-            None,
-            BLOCK_SIZE,
-            state.nft_contract.code_hash.clone(),
-            state.nft_contract.address.to_string(),
-        )?;
+    state.total_burned = state.total_burned + token_ids_mut.len()as u16;
 
-        state.total_burned = state.total_burned + token_ids_mut.len()as u16;
-
-        PAID_ADDRESSES_ITEM.save(deps.storage, &paid_addresses)?; 
-
-        //update public metadata first
-        let new_public_ext = 
-                if let Some(Metadata { extension, .. }) = group_master_meta.public_metadata {
-                    if let Some(mut ext) = extension {  
-                        //update name field
-                        ext.name = Some(pmsg.name.to_string());
-                        //add new images 
-                        for media in public_media_to_add.iter() { 
-                            ext.media.as_mut().unwrap().push(media.clone());
-                        } 
-                        let alpha_trait = ext.attributes.as_ref().unwrap().iter().find(|&x| x.trait_type == Some("Alpha".to_string()));
-                        if !alpha_trait.is_some(){
-                            return Err(ContractError::CustomError {val: "The main token id is not an Alpha".to_string()});  
-                        }  
-
-                        let current_xp_trait = ext.attributes.as_ref().unwrap().iter().find(|&x| x.trait_type == Some("XP".to_string())).unwrap();
-                        let current_xp = current_xp_trait.value.parse::<u32>().unwrap() + xp_total;
-                        let current_lvl_trait = ext.attributes.as_ref().unwrap().iter().find(|&x| x.trait_type == Some("LVL".to_string())).unwrap();
-                        let current_lvl = current_lvl_trait.value.parse::<u16>().unwrap();
-
-                        //add pack rank attribute to the public metadata if it doesnt exist
-                        if !ext.attributes.as_mut().unwrap().iter().any(|x| x.trait_type == Some("Pack Rank".to_string())){
-                            ext.attributes.as_mut().unwrap().push(Trait{
-                                trait_type: Some("Pack Rank".to_string()),
-                                value: "0".to_string(),
-                                display_type: None,
-                                max_value: None
-                            });
-                        } 
-
-                        let mut new_pack_size:u16 = 0;
-                        let mut new_pack_rank:u32 = 0;
-
-                        for attr in ext.attributes.as_mut().unwrap().iter_mut() {
-
-                            if attr.trait_type == Some("XP".to_string()) {
-                                attr.value = current_xp.to_string();
-                            }  
-                            if attr.trait_type == Some("Pack".to_string()) {
-                                new_pack_size = token_ids_mut.len()as u16 + attr.value.parse::<u16>().unwrap();
-                                attr.value = new_pack_size.to_string(); 
-                            }
-
-                            if attr.trait_type == Some("Pack Rank".to_string()) {
-                                new_pack_rank = pack_rank_total + attr.value.parse::<u32>().unwrap();
-                                attr.value = new_pack_rank.to_string();  
-                            }
-
-                            if attr.trait_type == Some("LVL".to_string()) {
-                                let shouldbe_lvl = if attr.value.parse::<u16>().unwrap() < state.level_cap {
-                                        levels.iter().find(|&x| x.xp_needed > current_xp).unwrap().level - 1
-                                    } 
-                                    else { 
-                                        attr.value.parse::<u16>().unwrap() 
-                                    }; 
-                                attr.value = shouldbe_lvl.to_string(); 
-
-                                if shouldbe_lvl > current_lvl {
-                                    response_attrs.push(("lvl_increase".to_string(), shouldbe_lvl.to_string()));
-                                }
-                            }  
-                        }
-                        
-                        //update store for the leaderboard
-                        PACK_MAIN_STORE.insert(deps.storage, &pmsg.main_token_id, &PackMain{
-                            token_id: pmsg.main_token_id.to_string(),
-                            pack_rank:  new_pack_rank,
-                            pack_count: new_pack_size,
-                            name: pmsg.name.to_string()
-                        })?;
-
-                        ext 
-                   }
-                    else {
-                        return Err(ContractError::CustomError {val: "unable to set metadata with uri".to_string()});
-                    }
-                } 
-                else {
-                    return Err(ContractError::CustomError {val: "unable to get metadata from nft contract".to_string()});
-                };
-             
-   
-        let new_privat_ext = 
-                if let Some(Metadata { extension, .. }) = group_master_meta.private_metadata {
-                    if let Some(mut ext) = extension {  
-                        for media in private_media_to_add.iter() { 
-                            ext.media.as_mut().unwrap().push(media.clone());
-                        }
-                        ext 
-                   }
-                    else {
-                        return Err(ContractError::CustomError {val: "unable to set metadata with uri".to_string()});
-                    }
-                } 
-                else {
-                    return Err(ContractError::CustomError {val: "unable to get metadata from nft contract".to_string()});
-                };
-        //add metadata update to responses
-        let cosmos_msg = set_metadata_msg(
-            pmsg.main_token_id.to_string(),
-            Some(Metadata {
-                token_uri: None,
-                extension: Some(new_public_ext),
-            }),
-            Some(Metadata {
-                token_uri: None,
-                extension: Some(new_privat_ext),
-            }), 
-            None,
-            BLOCK_SIZE,
-            state.nft_contract.code_hash.clone(),
-            state.nft_contract.address.to_string()
-        )?;
-        response_msgs.push(cosmos_msg); 
-
-        //enter history record
-        let history_token: HistoryToken = { HistoryToken {
-            wolf_main_token_id: pmsg.main_token_id.to_string(),
-            pack_member_token_ids: token_ids_mut.clone(),
-            pack_build_date: Some(_env.block.time.seconds())
-        }};
-        
-        history_store.push(deps.storage, &history_token)?;
-
-        
-        CONFIG_ITEM.save(deps.storage, &state)?; 
-     }
-     else{
-        return Err(ContractError::CustomError {val: "Not a valid contract address".to_string()});
-     }  
+    //add metadata update to responses
+    let cosmos_msg = set_metadata_msg(
+        pmsg.main_token_id.to_string(),
+        Some(Metadata {
+            token_uri: None,
+            extension: Some(plan.public_ext),
+        }),
+        Some(Metadata {
+            token_uri: None,
+            extension: Some(plan.private_ext),
+        }), 
+        None,
+        BLOCK_SIZE,
+        state.nft_contract.code_hash.clone(),
+        state.nft_contract.address.to_string()
+    )?;
+    response_msgs.push(cosmos_msg); 
+
+    //enter history record
+    let history_store = HISTORY_STORE.add_suffix(raw_address.as_slice());
+    let history_token: HistoryToken = { HistoryToken {
+        wolf_main_token_id: pmsg.main_token_id.to_string(),
+        pack_member_token_ids: token_ids_mut.clone(),
+        pack_build_date: Some(_env.block.time.seconds()),
+        kind: HistoryKind::Build
+    }};
+    
+    history_store.push(deps.storage, &history_token)?;
+
+    CONFIG_ITEM.save(deps.storage, &state)?; 
  
     // add transfer update to responses 
     response_msgs.push(transfer_nft_msg(
@@ -481,509 +506,4035 @@ pub fn join_pack(
    Ok(Response::new().add_messages(response_msgs).add_attributes(response_attrs))
 }
 
-// pub fn transfer_pack(
-//     _env: Env,
-//     deps: DepsMut,
-//     sender: &Addr,
-//     from: &Addr,
-//     token_ids: Vec<String>, 
-//     pmsg: PackTransferMsg
-// ) -> Result<Response, ContractError> {
-//     let mut response_msgs: Vec<CosmosMsg> = Vec::new();  
-//     let state = CONFIG_ITEM.load(deps.storage)?;    
-//     let mut pack_member = PackMember{ token_id: "".to_string(), rank: 0, attributes: Vec::new()};
-
-//     let holding: Vec<String> = INHOLDING_NFT_STORE.get(deps.storage,&deps.api.addr_canonicalize(&from.to_string())?).unwrap_or(Vec::new());
-//     if holding.len() != 0
-//     {
-//         return Err(ContractError::CustomError {val: "You need to claim back first".to_string()});
-//     }
-//     if !token_ids.iter().any(|x| x == &pmsg.main_token_id){
-//         return Err(ContractError::CustomError {val: "Main Token is not in the list".to_string()}); 
-//     } 
-
-//     if !token_ids.iter().any(|x| x == &pmsg.transfer_to_token_id){
-//         return Err(ContractError::CustomError {val: "Transfer To Token is not in the list".to_string()}); 
-//     }
- 
+// Outcome of adding members to an alpha, shared by join_pack and the build preview
+struct PackBuildPlan {
+    pack_members: Vec<PackMember>,
+    public_ext: Extension,
+    private_ext: Extension,
+    xp: u32,
+    level: u16,
+    previous_level: u16,
+    pack_size: u16,
+    pack_rank: u32,
+    pack_rank_change: u32
+}
 
-//     let mut main_pack_members = PACK_MEMBER_STORE.get(deps.storage, &pmsg.main_token_id).ok_or_else(|| StdError::generic_err("This tokenid doesn't exist"))?;
-//     let mut transfer_to_pack_members = PACK_MEMBER_STORE.get(deps.storage, &pmsg.transfer_to_token_id).unwrap_or_else(Vec::new);
-//     let pos = main_pack_members.iter().position(|x| x.token_id == pmsg.token_id);
-//     if pos.is_none(){
-//         return Err(ContractError::CustomError {val: "Token is not a pack member".to_string()});  
-//     }
-//     else{
-//         pack_member = main_pack_members.remove(pos.unwrap());
-//         transfer_to_pack_members.push(pack_member.clone());
-//     }
-    
-        
-//     PACK_MEMBER_STORE.insert(deps.storage, &pmsg.main_token_id, &main_pack_members)?;
-//     PACK_MEMBER_STORE.insert(deps.storage, &pmsg.transfer_to_token_id, &transfer_to_pack_members)?;
-     
- 
-    
-//     if sender == &state.nft_contract.address{ 
-
-//         let history_store = HISTORY_STORE.add_suffix(from.to_string().as_bytes());
-       
-//         // Get viewing key for NFTs
-//         let viewer = Some(ViewerInfo {
-//             address: _env.contract.address.to_string(),
-//             viewing_key: state.viewing_key.as_ref().unwrap().to_string(),
-//         });
-
-//         let main_meta: NftDossier =  nft_dossier_query(
-//             deps.querier,
-//             pmsg.main_token_id.to_string(),
-//             viewer.clone(),
-//             None,
-//             BLOCK_SIZE,
-//             state.nft_contract.code_hash.clone(),
-//             state.nft_contract.address.to_string(),
-//         )?;
-
-//         let transfer_to_meta: NftDossier =  nft_dossier_query(
-//             deps.querier,
-//             pmsg.transfer_to_token_id.to_string(),
-//             viewer.clone(),
-//             None,
-//             BLOCK_SIZE,
-//             state.nft_contract.code_hash.clone(),
-//             state.nft_contract.address.to_string(),
-//         )?;
-//         let main_alpha_trait = main_meta.public_metadata.as_ref().unwrap().extension.as_ref().unwrap().attributes.as_ref().unwrap().iter().find(|&x| x.trait_type == Some("Alpha".to_string()));
-//         if main_alpha_trait.is_none(){
-//             return Err(ContractError::CustomError {val: "You can't do this action with a non-alpha".to_string()});  
-//         } 
-//         let transfer_to_alpha_trait = transfer_to_meta.public_metadata.as_ref().unwrap().extension.as_ref().unwrap().attributes.as_ref().unwrap().iter().find(|&x| x.trait_type == Some("Alpha".to_string()));
-//         if transfer_to_alpha_trait.is_none(){
-//             return Err(ContractError::CustomError {val: "You can't do this action with a non-alpha".to_string()});  
-//         }  
-//         let mut pub_media_file = MediaFile::default();
-//         let mut priv_media_file = MediaFile::default();
-
-//         //update public metadata first
-//         let new_public_ext = 
-//                 if let Some(Metadata { extension, .. }) = main_meta.public_metadata {
-//                     if let Some(mut ext) = extension {  
-//                         //remove image of token being transfered
-//                         pub_media_file = ext.media.as_mut().unwrap().remove((pmsg.member_index+1)as usize);
-//                         //update rank
-//                         let mut new_pack_rank: u32 = 0;
-//                         let mut new_pack_size: u16 = 0;
-                        
-//                         for attr in ext.attributes.as_mut().unwrap().iter_mut() {
- 
-//                             if attr.trait_type == Some("Pack".to_string()) {
-//                                 new_pack_size = attr.value.parse::<u16>().unwrap() - 1;
-//                                 attr.value = new_pack_size.to_string(); 
-//                             }
-
-//                             if attr.trait_type == Some("Pack Rank".to_string()) { 
-//                                 new_pack_rank = attr.value.parse::<u32>().unwrap() - (state.collection_size - pack_member.rank)as u32;
-//                                 attr.value = new_pack_rank.to_string();  
-//                             } 
-//                         }
-//                         let mut pack_main = PACK_MAIN_STORE.get(deps.storage, &pmsg.main_token_id).unwrap();
-//                         pack_main.pack_rank = new_pack_rank;
-//                         pack_main.pack_count = new_pack_size;
-             
-//                         //update store for the leaderboard
-//                         PACK_MAIN_STORE.insert(deps.storage, &pmsg.main_token_id, &pack_main)?;
-
-//                         ext 
-//                    }
-//                     else {
-//                         return Err(ContractError::CustomError {val: "unable to set metadata with uri".to_string()});
-//                     }
-//                 } 
-//                 else {
-//                     return Err(ContractError::CustomError {val: "unable to get metadata from nft contract".to_string()});
-//                 };
-             
-   
-//         let new_private_ext = 
-//                 if let Some(Metadata { extension, .. }) = main_meta.private_metadata {
-//                     if let Some(mut ext) = extension {  
-//                         priv_media_file = ext.media.as_mut().unwrap().remove(pmsg.member_index as usize);
-//                         ext 
-//                    }
-//                     else {
-//                         return Err(ContractError::CustomError {val: "unable to set metadata with uri".to_string()});
-//                     }
-//                 } 
-//                 else {
-//                     return Err(ContractError::CustomError {val: "unable to get metadata from nft contract".to_string()});
-//                 };
- 
-//         let mut pack_member_token_ids: Vec<String> = Vec::new();
-//         pack_member_token_ids.push(pmsg.token_id.clone());
-//         //enter history record
-//         let history_token: HistoryToken = { HistoryToken {
-//             wolf_main_token_id: pmsg.transfer_to_token_id.to_string(),
-//             pack_member_token_ids: pack_member_token_ids,
-//             pack_build_date: Some(_env.block.time.seconds())
-//         }};
-        
-//         history_store.push(deps.storage, &history_token)?;
-
-//         //move member to new alpha
-//         let new_transfer_public_ext = 
-//         if let Some(Metadata { extension, .. }) = transfer_to_meta.public_metadata {
-//             if let Some(mut ext) = extension {   
-//                 ext.media.as_mut().unwrap().push(pub_media_file.clone());
-//                 //add pack rank attribute to the public metadata if it doesnt exist
-//                 if !ext.attributes.as_mut().unwrap().iter().any(|x| x.trait_type == Some("Pack Rank".to_string())){
-//                     ext.attributes.as_mut().unwrap().push(Trait{
-//                         trait_type: Some("Pack Rank".to_string()),
-//                         value: "0".to_string(),
-//                         display_type: None,
-//                         max_value: None
-//                     });
-//                 } 
-//                 //update rank
-//                 let mut new_pack_rank: u32 = 0;
-//                 let mut new_pack_size: u16 = 0;
-                
-//                 for attr in ext.attributes.as_mut().unwrap().iter_mut() {
-
-//                     if attr.trait_type == Some("Pack".to_string()) {
-//                         new_pack_size = attr.value.parse::<u16>().unwrap() + 1;
-//                         attr.value = new_pack_size.to_string(); 
-//                     }
-
-//                     if attr.trait_type == Some("Pack Rank".to_string()) { 
-//                         new_pack_rank = attr.value.parse::<u32>().unwrap() + (state.collection_size - pack_member.rank) as u32;
-//                         attr.value = new_pack_rank.to_string();  
-//                     } 
-//                 }
-//                 let mut pack_transfer_to = PACK_MAIN_STORE.get(deps.storage, &pmsg.transfer_to_token_id)
-//                 .unwrap_or(PackMain{
-//                     token_id: pmsg.transfer_to_token_id.to_string(),
-//                     pack_rank:  0,
-//                     pack_count: 0,
-//                     name: "".to_string()
-//                 });
-//                 pack_transfer_to.pack_rank = new_pack_rank;
-//                 pack_transfer_to.pack_count = new_pack_size;
-     
-//                 //update store for the leaderboard 
-//                 PACK_MAIN_STORE.insert(deps.storage, &pmsg.transfer_to_token_id, &pack_transfer_to)?;
-                
-//                 ext 
-//            }
-//             else {
-//                 return Err(ContractError::CustomError {val: "unable to set metadata with uri".to_string()});
-//             }
-//         } 
-//         else {
-//             return Err(ContractError::CustomError {val: "unable to get metadata from nft contract".to_string()});
-//         };
-
-//         let new_transfer_privat_ext = 
-//         if let Some(Metadata { extension, .. }) = transfer_to_meta.private_metadata {
-//             if let Some(mut ext) = extension {  
-//                 ext.media.as_mut().unwrap().push(priv_media_file.clone());
-//                 ext 
-//            }
-//             else {
-//                 return Err(ContractError::CustomError {val: "unable to set metadata with uri".to_string()});
-//             }
-//         } 
-//         else {
-//             return Err(ContractError::CustomError {val: "unable to get metadata from nft contract".to_string()});
-//         };
-
-//                 //add metadata update to responses 
-//                 response_msgs.push(
-//                     set_metadata_msg(
-//                         pmsg.main_token_id.to_string(),
-//                         Some(Metadata {
-//                             token_uri: None,
-//                             extension: Some(new_public_ext),
-//                         }),
-//                         Some(Metadata {
-//                             token_uri: None,
-//                             extension: Some(new_private_ext),
-//                         }), 
-//                         None,
-//                         BLOCK_SIZE,
-//                         state.nft_contract.code_hash.clone(),
-//                         state.nft_contract.address.to_string()
-//                     )?
-//                 );  
-//                 response_msgs.push(
-//                     set_metadata_msg(
-//                         pmsg.transfer_to_token_id.to_string(),
-//                         Some(Metadata {
-//                             token_uri: None,
-//                             extension: Some(new_transfer_public_ext),
-//                         }),
-//                         Some(Metadata {
-//                             token_uri: None,
-//                             extension: Some(new_transfer_privat_ext),
-//                         }), 
-//                         None,
-//                         BLOCK_SIZE,
-//                         state.nft_contract.code_hash.clone(),
-//                         state.nft_contract.address.to_string()
-//                     )?
-//                 ); 
-//                 INHOLDING_NFT_STORE.insert(deps.storage, &deps.api.addr_canonicalize(&from.to_string())?, &token_ids)?; 
-//      }
-//      else{
-//         return Err(ContractError::CustomError {val: "Not a valid contract address".to_string()});
-//      }  
- 
-//    Ok(Response::new().add_messages(response_msgs))
-// }
-
-// pub fn try_claim_back(
-//     deps: DepsMut,
-//     _env: Env,
-//     sender: &Addr
-// ) -> Result<Response, ContractError> {  
-//     let state = CONFIG_ITEM.load(deps.storage)?;
-//     let alphas: Vec<String> = INHOLDING_NFT_STORE.get(deps.storage,&deps.api.addr_canonicalize(&sender.to_string())?).ok_or_else(|| StdError::generic_err("no tokens found"))?;
-//     let mut transfers: Vec<Transfer> = Vec::new();
-//                 transfers.push(
-//                     Transfer{
-//                         recipient: sender.to_string(),
-//                         token_ids: alphas,
-//                         memo: None
-//                     }
-//                 );
-//     INHOLDING_NFT_STORE.remove(deps.storage, &deps.api.addr_canonicalize(&sender.to_string())?)?;         
-
-//     Ok(Response::new()
-//         .add_message(batch_transfer_nft_msg(
-//             transfers,
-//             None,
-//             BLOCK_SIZE,
-//             state.nft_contract.code_hash.clone(),
-//             state.nft_contract.address.to_string(),
-//         )?)
-//     )
-// }
+struct PlannedMember {
+    member: PackMember,
+    xp: u32,
+    public_media: MediaFile,
+    private_media: Option<MediaFile>
+}
 
-pub fn try_send_nft_back(
-    deps: DepsMut,
-    _env: Env,
-    sender: &Addr,
-    token_id: String,
-    owner: Addr
-) -> Result<Response, ContractError> { 
-    let state = CONFIG_ITEM.load(deps.storage)?;
+// Checks a single token joining a pack, a preview runs it for every member to report each failure
+fn plan_member(
+    deps: Deps,
+    state: &State,
+    levels: &[Level],
+    required_xp: u32,
+    viewer: &Option<ViewerInfo>,
+    token_id: &str,
+    holder: Option<&Addr>
+) -> Result<PlannedMember, ContractError> {
+    let rank: u16 = RANK_STORE.get(deps.storage, &token_id.to_string())
+    .ok_or_else(|| ContractError::RankNotFound { token_id: token_id.to_string() })?;
 
-    if sender.clone() != state.owner {
-        return Err(ContractError::CustomError {val: "You don't have the permissions to execute this command".to_string()});
-    }  
- 
-    Ok(Response::new()
-        .add_message(transfer_nft_msg(
-            owner.to_string(),
-            token_id.to_string(),
-            None,
-            None,
-            BLOCK_SIZE,
-            state.nft_contract.code_hash.to_string(),
-            state.nft_contract.address.to_string()
-        )?)
-    )
-}
+    let wolf_meta: NftDossier =  nft_dossier_query(
+        deps.querier,
+        token_id.to_string(),
+        viewer.clone(),
+        None,
+        BLOCK_SIZE,
+        state.nft_contract.code_hash.clone(),
+        state.nft_contract.address.to_string(),
+    )?;
+    check_holder(token_id, &wolf_meta, holder)?;
+    let (public_ext, private_ext) = get_plan_extensions(token_id, wolf_meta, holder.is_none())?;
+    if has_trait(&public_ext, &state.trait_names.alpha){
+        return Err(ContractError::CombineAlphas { token_id: token_id.to_string() });  
+    } 
+    let pub_attributes = get_attributes(token_id, &public_ext)?.clone();
+    let current_xp: u32 = parse_trait(token_id, &public_ext, &state.trait_names.xp)?;
+    //check that lvl/xp is high enough to be added to the pack
+    if current_xp < required_xp{
+        return Err(ContractError::LevelTooLow { token_id: token_id.to_string(), level: level_for_xp(levels, current_xp), required: level_for_xp(levels, required_xp) });  
+    }
+    let public_media = first_media(token_id, &public_ext, "public")?;
+    let private_media = match &private_ext {
+        Some(private_ext) => Some(first_media(token_id, private_ext, "private")?),
+        None => None
+    };
 
-fn try_revoke_permit(
-    deps: DepsMut,
-    sender: &Addr,
-    permit_name: &str,
-) -> Result<Response, ContractError> {
-    RevokedPermits::revoke_permit(deps.storage, PREFIX_REVOKED_PERMITS, &sender.to_string(), permit_name);
-    
-    Ok(Response::default())
+    Ok(PlannedMember {
+        member: PackMember{
+            token_id: token_id.to_string(),
+            rank:  rank,
+            attributes: pub_attributes
+        },
+        xp: current_xp,
+        public_media,
+        private_media
+    })
 }
 
-fn try_add_payment(
-    deps: DepsMut,
-    sender: &Addr,
-    payment: PaymentContractInfo
-) -> Result<Response, ContractError> { 
-    let mut state = CONFIG_ITEM.load(deps.storage)?;
+fn plan_pack_build(
+    deps: Deps,
+    env: &Env,
+    state: &State,
+    main_token_id: &str,
+    member_ids: &[String],
+    name: Option<&str>,
+    holder: Option<&Addr>
+) -> Result<PackBuildPlan, ContractError> {
+    let levels = LEVEL_ITEM.load(deps.storage)?;   
+    let mut pack_members = PACK_MEMBER_STORE.get(deps.storage, &main_token_id.to_string()).unwrap_or_else(Vec::new);
+    //a preview runs while the owner still holds the tokens, so their private metadata may not be visible
+    let preview = holder.is_none();
 
-    if sender.clone() != state.owner {
-        return Err(ContractError::CustomError {val: "You don't have the permissions to execute this command".to_string()});
-    }  
+    //Check that the new members fit in the pack
+    let slots_left = state.pack_max.saturating_sub(pack_members.len() as u16);
+    if member_ids.len() > slots_left as usize {
+        return Err(ContractError::PackFull { main_token_id: main_token_id.to_string(), slots_left: slots_left });  
+    }
 
-    if state.valid_payments.is_none() {
-        state.valid_payments = Some(vec![]);
+    // Get viewing key for NFTs
+    let viewer = Some(ViewerInfo {
+        address: env.contract.address.to_string(),
+        viewing_key: state.viewing_key.as_ref().unwrap().to_string(),
+    });
+
+    let mut public_media_to_add: Vec<MediaFile> = Vec::new();
+    let mut private_media_to_add: Vec<MediaFile> = Vec::new();
+    let mut xp_total: u32 = 0;
+    let required_xp = required_member_xp(state, &levels)?;
+
+    for token_id in member_ids.iter() { 
+        let planned = plan_member(deps, state, &levels, required_xp, &viewer, token_id, holder)?;
+        xp_total = xp_total.checked_add(planned.xp)
+            .ok_or_else(|| ContractError::InvalidTrait { token_id: token_id.to_string(), trait_type: state.trait_names.xp.to_string(), value: planned.xp.to_string() })?;
+        public_media_to_add.push(planned.public_media);
+        if let Some(private_media) = planned.private_media {
+            private_media_to_add.push(private_media);
+        }
+        pack_members.push(planned.member);
     }
-    let payment_contract = state.valid_payments.as_ref().unwrap().iter().find(|x| x.name == payment.name);
-    if payment_contract.is_some(){
-        return Err(ContractError::CustomError {val: "This payment name already exists".to_string()});  
+
+    // ------------------------------------------
+    //        Master NFT IMAGE AND XP UPDATE
+    // ------------------------------------------
+    let group_master_meta: NftDossier =  nft_dossier_query(
+        deps.querier,
+        main_token_id.to_string(),
+        viewer,
+        None,
+        BLOCK_SIZE,
+        state.nft_contract.code_hash.clone(),
+        state.nft_contract.address.to_string(),
+    )?;
+
+    check_holder(main_token_id, &group_master_meta, holder)?;
+    let (mut public_ext, private_ext) = get_plan_extensions(main_token_id, group_master_meta, preview)?;
+    let mut private_ext = private_ext.unwrap_or_default();
+
+    //update public metadata first
+    if !has_trait(&public_ext, &state.trait_names.alpha){
+        return Err(ContractError::NotAlpha { token_id: main_token_id.to_string() });  
     }  
-    state.valid_payments.unwrap().push(payment);
-    Ok(Response::default())
+    //update name field
+    if let Some(name) = name {
+        public_ext.name = Some(name.to_string());
+    }
+    //add new images 
+    public_ext.media.get_or_insert_with(Vec::new).extend(public_media_to_add);
+
+    let main_xp: u32 = parse_trait(main_token_id, &public_ext, &state.trait_names.xp)?;
+    let current_xp = main_xp.checked_add(xp_total)
+        .ok_or_else(|| ContractError::InvalidTrait { token_id: main_token_id.to_string(), trait_type: state.trait_names.xp.to_string(), value: main_xp.to_string() })?;
+    let current_lvl: u16 = parse_trait(main_token_id, &public_ext, &state.trait_names.level)?;
+    //pack and pack rank start at 0 for alphas that haven't built a pack yet
+    let current_pack_size: u16 = if has_trait(&public_ext, &state.trait_names.pack) {
+        parse_trait(main_token_id, &public_ext, &state.trait_names.pack)?
+    } else { 0 };
+    let current_pack_rank: u32 = if has_trait(&public_ext, &state.trait_names.pack_rank) {
+        parse_trait(main_token_id, &public_ext, &state.trait_names.pack_rank)?
+    } else { 0 };
+
+    let new_pack_size: u16 = current_pack_size.checked_add(member_ids.len() as u16)
+        .ok_or_else(|| ContractError::InvalidTrait { token_id: main_token_id.to_string(), trait_type: state.trait_names.pack.to_string(), value: current_pack_size.to_string() })?;
+    let new_pack_rank: u32 = calculate_pack_rank(state, &pack_members);
+    let new_lvl = if current_lvl < state.level_cap {
+            level_for_xp(&levels, current_xp)
+        } 
+        else { 
+            current_lvl 
+        }; 
+
+    set_trait(&mut public_ext, &state.trait_names.xp, current_xp.to_string());
+    set_trait(&mut public_ext, &state.trait_names.pack, new_pack_size.to_string());
+    set_trait(&mut public_ext, &state.trait_names.pack_rank, new_pack_rank.to_string());
+    set_trait(&mut public_ext, &state.trait_names.level, new_lvl.to_string());
+
+    private_ext.media.get_or_insert_with(Vec::new).extend(private_media_to_add);
+
+    Ok(PackBuildPlan{
+        pack_members: pack_members,
+        public_ext: public_ext,
+        private_ext: private_ext,
+        xp: current_xp,
+        level: new_lvl,
+        previous_level: current_lvl,
+        pack_size: new_pack_size,
+        pack_rank: new_pack_rank,
+        pack_rank_change: new_pack_rank.saturating_sub(current_pack_rank)
+    })
 }
 
-fn try_remove_payment(
+pub fn transfer_pack(
+    _env: Env,
     deps: DepsMut,
     sender: &Addr,
-    payment_name: String,
+    from: &Addr,
+    token_ids: Vec<String>, 
+    pmsg: PackTransferMsg
 ) -> Result<Response, ContractError> {
-    let mut state = CONFIG_ITEM.load(deps.storage)?;
-
-    if sender.clone() != state.owner {
-        return Err(ContractError::CustomError {val: "You don't have the permissions to execute this command".to_string()});
-    }  
+    let mut response_msgs: Vec<CosmosMsg> = Vec::new();  
+    let state = CONFIG_ITEM.load(deps.storage)?;    
 
-    if state.valid_payments.is_none() {
-        return Err(ContractError::CustomError {val: "No valid payments exist".to_string()});
+    if sender != &state.nft_contract.address{
+        return Err(ContractError::InvalidNftContract { address: sender.to_string() });
     }
 
-    let position = state.valid_payments.as_ref().unwrap().iter().position(|x| x.name == payment_name);
-         
-    if position.is_none(){
-        return Err(ContractError::CustomError {val: "Payment name doesn't exist".to_string()});  
+    if pmsg.main_token_id == pmsg.transfer_to_token_id{
+        return Err(ContractError::SameAlpha { token_id: pmsg.main_token_id.to_string() }); 
     }
-    else{ 
-        state.valid_payments.as_mut().unwrap().remove(position.unwrap());
+
+    if !token_ids.iter().any(|x| x == &pmsg.main_token_id){
+        return Err(ContractError::MainTokenNotInList { token_id: pmsg.main_token_id.to_string() }); 
+    } 
+
+    if !token_ids.iter().any(|x| x == &pmsg.transfer_to_token_id){
+        return Err(ContractError::TokenNotInList { token_id: pmsg.transfer_to_token_id.to_string() }); 
     }
-    
-    CONFIG_ITEM.save(deps.storage, &state)?;
-    Ok(Response::default())
-}
 
-#[entry_point]
-pub fn query(
-    deps: Deps,
-    _env: Env,
-    msg: QueryMsg,
-) -> StdResult<Binary> {
-    match msg {   
-        QueryMsg::GetPackBuildInfo {} => to_binary(&query_pack_build_info(deps)?),  
-        QueryMsg::GetNumUserHistory { permit } => to_binary(&query_num_user_history(deps, permit)?),
-        QueryMsg::GetUserHistory {permit, start_page, page_size} => to_binary(&query_user_history(deps, permit, start_page, page_size)?),
-        QueryMsg::GetNumPacks { } => to_binary(&query_num_packs(deps)?),
-        QueryMsg::GetPacks { start_page, page_size } => to_binary(&query_packs(deps, start_page, page_size)?),
-        QueryMsg::GetPackMembers { main_token_id } => to_binary(&query_pack_members(deps, main_token_id )?),
-        QueryMsg::GetPackMembersTraits { main_token_id } => to_binary(&query_pack_member_traits(deps, main_token_id )?),
-        QueryMsg::GetHolding { addr } => to_binary(&query_holding(deps, addr)?),
+    let mut main_pack_members = PACK_MEMBER_STORE.get(deps.storage, &pmsg.main_token_id).ok_or_else(|| ContractError::NoPack { token_id: pmsg.main_token_id.to_string() })?;
+    let mut transfer_to_pack_members = PACK_MEMBER_STORE.get(deps.storage, &pmsg.transfer_to_token_id).unwrap_or_else(Vec::new);
+    let main_pack_size = main_pack_members.len();
+    let transfer_to_pack_size = transfer_to_pack_members.len();
+
+    if transfer_to_pack_members.len() >= state.pack_max as usize {
+        return Err(ContractError::PackFull { main_token_id: pmsg.transfer_to_token_id.to_string(), slots_left: 0 }); 
     }
-}
 
- // Get pack members, get distinct traits 
-fn query_pack_build_info(
-    deps: Deps,
-) -> StdResult<BuildInfoResponse> { 
-    let state = CONFIG_ITEM.load(deps.storage)?;
+    let member_index = main_pack_members.iter().position(|x| x.token_id == pmsg.token_id)
+        .ok_or_else(|| ContractError::NotPackMember { token_id: pmsg.token_id.to_string(), main_token_id: pmsg.main_token_id.to_string() })?;
+    let pack_member = main_pack_members.remove(member_index);
+    transfer_to_pack_members.push(pack_member);
 
-    Ok(BuildInfoResponse { pack_max: state.pack_max, total_burned: state.total_burned, valid_payments: state.valid_payments })
-} 
- 
-fn query_num_user_history(
-    deps: Deps, 
-    permit: Permit
-) -> StdResult<u32> { 
-    let (user_raw, my_addr) = get_querier(deps, permit)?;
-    let history_store = HISTORY_STORE.add_suffix(&user_raw);
-    let num = history_store.get_len(deps.storage)?;
-    Ok(num)
-}  
+    let new_main_pack_rank = calculate_pack_rank(&state, &main_pack_members);
+    let new_transfer_to_pack_rank = calculate_pack_rank(&state, &transfer_to_pack_members);
 
-fn query_user_history(
-    deps: Deps, 
-    permit: Permit,
-    start_page: u32, 
-    page_size: u32
-) -> StdResult<Vec<HistoryToken>> {
-    let (user_raw, my_addr) = get_querier(deps, permit)?;
-    
-    let history_store = HISTORY_STORE.add_suffix(&user_raw); 
-    let history = history_store.paging(deps.storage, start_page, page_size)?;
-    Ok(history)
-} 
+    // Get viewing key for NFTs
+    let viewer = Some(ViewerInfo {
+        address: _env.contract.address.to_string(),
+        viewing_key: state.viewing_key.as_ref().unwrap().to_string(),
+    });
 
-fn query_num_packs(
-    deps: Deps
-) -> StdResult<u32> {
-    let num_staked_keys = PACK_MAIN_STORE.get_len(deps.storage).unwrap();
-    Ok(num_staked_keys)
-}
+    let main_meta: NftDossier =  nft_dossier_query(
+        deps.querier,
+        pmsg.main_token_id.to_string(),
+        viewer.clone(),
+        None,
+        BLOCK_SIZE,
+        state.nft_contract.code_hash.clone(),
+        state.nft_contract.address.to_string(),
+    )?;
 
-fn query_packs(
-    deps: Deps, 
-    start_page: u32, 
-    page_size: u32
-) -> StdResult<Vec<PackMain>> {
-    let packs = PACK_MAIN_STORE.paging(deps.storage, start_page, page_size)?; 
+    let transfer_to_meta: NftDossier =  nft_dossier_query(
+        deps.querier,
+        pmsg.transfer_to_token_id.to_string(),
+        viewer.clone(),
+        None,
+        BLOCK_SIZE,
+        state.nft_contract.code_hash.clone(),
+        state.nft_contract.address.to_string(),
+    )?;
 
-    let mut packs_mut: Vec<PackMain> = Vec::new();
+    check_holder(&pmsg.main_token_id, &main_meta, Some(&_env.contract.address))?;
+    check_holder(&pmsg.transfer_to_token_id, &transfer_to_meta, Some(&_env.contract.address))?;
+    let (mut main_public_ext, mut main_private_ext) = get_extensions(&pmsg.main_token_id, main_meta)?;
+    let (mut transfer_to_public_ext, mut transfer_to_private_ext) = get_extensions(&pmsg.transfer_to_token_id, transfer_to_meta)?;
+
+    if !has_trait(&main_public_ext, &state.trait_names.alpha){
+        return Err(ContractError::NotAlpha { token_id: pmsg.main_token_id.to_string() });  
+    }
+    if !has_trait(&transfer_to_public_ext, &state.trait_names.alpha){
+        return Err(ContractError::NotAlpha { token_id: pmsg.transfer_to_token_id.to_string() });  
+    }
+
+    //move the member's images from the main alpha to the transfer to alpha
+    let public_media_file = remove_member_media(&pmsg.main_token_id, &mut main_public_ext, main_pack_size, member_index)?;
+    let private_media_file = remove_member_media(&pmsg.main_token_id, &mut main_private_ext, main_pack_size, member_index)?;
+    add_member_media(&pmsg.transfer_to_token_id, &mut transfer_to_public_ext, transfer_to_pack_size, public_media_file)?;
+    add_member_media(&pmsg.transfer_to_token_id, &mut transfer_to_private_ext, transfer_to_pack_size, private_media_file)?;
+
+    set_trait(&mut main_public_ext, &state.trait_names.pack, main_pack_members.len().to_string());
+    set_trait(&mut main_public_ext, &state.trait_names.pack_rank, new_main_pack_rank.to_string());
+    set_trait(&mut transfer_to_public_ext, &state.trait_names.pack, transfer_to_pack_members.len().to_string());
+    set_trait(&mut transfer_to_public_ext, &state.trait_names.pack_rank, new_transfer_to_pack_rank.to_string());
+
+    //the moved member is saved first so removing an emptied pack leaves it in place
+    save_pack_members(deps.storage, &pmsg.transfer_to_token_id, &transfer_to_pack_members)?;
+
+    //update store for the leaderboard, a pack whose last member left is removed
+    if main_pack_members.is_empty() {
+        remove_pack_members(deps.storage, &pmsg.main_token_id)?;
+        remove_pack_main(deps.storage, &pmsg.main_token_id)?;
+    }
+    else {
+        save_pack_members(deps.storage, &pmsg.main_token_id, &main_pack_members)?;
+        let mut pack_main = PACK_MAIN_STORE.get(deps.storage, &pmsg.main_token_id)
+        .unwrap_or(PackMain{
+            token_id: pmsg.main_token_id.to_string(),
+            pack_rank: 0,
+            pack_count: 0,
+            name: main_public_ext.name.clone().unwrap_or_default()
+        });
+        pack_main.pack_rank = new_main_pack_rank;
+        pack_main.pack_count = main_pack_members.len() as u16;
+        save_pack_main(deps.storage, &state, &pack_main)?;
+    }
+
+    let mut pack_transfer_to = PACK_MAIN_STORE.get(deps.storage, &pmsg.transfer_to_token_id)
+    .unwrap_or(PackMain{
+        token_id: pmsg.transfer_to_token_id.to_string(),
+        pack_rank: 0,
+        pack_count: 0,
+        name: transfer_to_public_ext.name.clone().unwrap_or_default()
+    });
+    pack_transfer_to.pack_rank = new_transfer_to_pack_rank;
+    pack_transfer_to.pack_count = transfer_to_pack_members.len() as u16;
+    save_pack_main(deps.storage, &state, &pack_transfer_to)?;
+
+    //enter history record
+    let raw_address = deps.api.addr_canonicalize(from.as_str())?;
+    let history_store = HISTORY_STORE.add_suffix(raw_address.as_slice());
+    let history_token: HistoryToken = { HistoryToken {
+        wolf_main_token_id: pmsg.transfer_to_token_id.to_string(),
+        pack_member_token_ids: vec![pmsg.token_id.to_string()],
+        pack_build_date: Some(_env.block.time.seconds()),
+        kind: HistoryKind::Transfer
+    }};
+    history_store.push(deps.storage, &history_token)?;
+
+    //add metadata updates to responses 
+    response_msgs.push(
+        set_metadata_msg(
+            pmsg.main_token_id.to_string(),
+            Some(Metadata {
+                token_uri: None,
+                extension: Some(main_public_ext),
+            }),
+            Some(Metadata {
+                token_uri: None,
+                extension: Some(main_private_ext),
+            }), 
+            None,
+            BLOCK_SIZE,
+            state.nft_contract.code_hash.clone(),
+            state.nft_contract.address.to_string()
+        )?
+    );  
+    response_msgs.push(
+        set_metadata_msg(
+            pmsg.transfer_to_token_id.to_string(),
+            Some(Metadata {
+                token_uri: None,
+                extension: Some(transfer_to_public_ext),
+            }),
+            Some(Metadata {
+                token_uri: None,
+                extension: Some(transfer_to_private_ext),
+            }), 
+            None,
+            BLOCK_SIZE,
+            state.nft_contract.code_hash.clone(),
+            state.nft_contract.address.to_string()
+        )?
+    ); 
+
+    //send both alphas back in the same transaction
+    response_msgs.push(batch_transfer_nft_msg(
+        vec![Transfer{
+            recipient: from.to_string(),
+            token_ids: token_ids,
+            memo: None
+        }],
+        None,
+        BLOCK_SIZE,
+        state.nft_contract.code_hash.clone(),
+        state.nft_contract.address.to_string(),
+    )?);
  
-    for (index, (key_value, value)) in packs.iter().enumerate() {
-        packs_mut.push(value.clone());
-    } 
-  
-    Ok(packs_mut)
+   Ok(Response::new().add_messages(response_msgs))
 }
- 
-fn query_pack_members(
-    deps: Deps, 
-    main_token_id: String
-) -> StdResult<Vec<PackMember>> {
-    let pack_members = PACK_MEMBER_STORE.get(deps.storage, &main_token_id).unwrap_or_else(Vec::new);
-    Ok(pack_members)
+
+fn check_unique(
+    token_ids: &[String]
+) -> Result<(), ContractError> {
+    for (index, token_id) in token_ids.iter().enumerate() {
+        if token_ids[..index].contains(token_id) {
+            return Err(ContractError::DuplicateToken { token_id: token_id.to_string() });
+        }
+    }
+    Ok(())
 }
 
-fn query_pack_member_traits(
-    deps: Deps, 
-    main_token_id: String
-) -> StdResult<Vec<Trait>> {
-    
-    let pack_members = PACK_MEMBER_STORE.get(deps.storage, &main_token_id).unwrap_or_else(Vec::new);
-    let mut distinct_traits: Vec<Trait> = Vec::new();
-    for (index, value) in pack_members.iter().enumerate() {
-        for member_trait in &value.attributes {
-            if !distinct_traits.contains(&member_trait) {
-                distinct_traits.push(member_trait.clone());
-            }
+// Tokens of a build must be held by the given address, which for a real build is
+// this contract since the nft contract transfers them before calling BatchReceiveNft
+fn check_holder(
+    token_id: &str,
+    meta: &NftDossier,
+    holder: Option<&Addr>
+) -> Result<(), ContractError> {
+    if let Some(holder) = holder {
+        if meta.owner.as_ref() != Some(holder) {
+            return Err(ContractError::TokenNotReceived { token_id: token_id.to_string() });
         }
     }
-    Ok(distinct_traits)
+    Ok(())
 }
 
-fn query_holding(
-    deps: Deps, 
-    addr: Addr
-) -> StdResult<Vec<String>> { 
-    let holding: Vec<String> = INHOLDING_NFT_STORE.get(deps.storage,&deps.api.addr_canonicalize(&addr.to_string())?).unwrap_or(Vec::new());
-    Ok(holding)
+pub fn disband_pack(
+    _env: Env,
+    deps: DepsMut,
+    sender: &Addr,
+    from: &Addr,
+    token_ids: Vec<String>, 
+    pmsg: PackDisbandMsg
+) -> Result<Response, ContractError> {
+    let mut response_msgs: Vec<CosmosMsg> = Vec::new();
+    let mut response_attrs = vec![];
+    let state = CONFIG_ITEM.load(deps.storage)?;
+
+    if sender != &state.nft_contract.address{
+        return Err(ContractError::InvalidNftContract { address: sender.to_string() });
+    }
+    if !token_ids.contains(&pmsg.main_token_id) {
+        return Err(ContractError::MainTokenNotInList { token_id: pmsg.main_token_id.to_string() });  
+    }
+    //only the alpha is sent back, anything else would be stuck in the contract
+    if let Some(token_id) = token_ids.iter().find(|x| x != &&pmsg.main_token_id) {
+        return Err(ContractError::UnexpectedToken { token_id: token_id.to_string() });
+    }
+
+    let pack_members = PACK_MEMBER_STORE.get(deps.storage, &pmsg.main_token_id).unwrap_or_default();
+    if pack_members.is_empty() {
+        return Err(ContractError::NoPack { token_id: pmsg.main_token_id.to_string() });  
+    }
+
+    let viewer = Some(ViewerInfo {
+        address: _env.contract.address.to_string(),
+        viewing_key: state.viewing_key.as_ref().unwrap().to_string(),
+    });
+    let main_meta: NftDossier =  nft_dossier_query(
+        deps.querier,
+        pmsg.main_token_id.to_string(),
+        viewer,
+        None,
+        BLOCK_SIZE,
+        state.nft_contract.code_hash.clone(),
+        state.nft_contract.address.to_string(),
+    )?;
+    check_holder(&pmsg.main_token_id, &main_meta, Some(&_env.contract.address))?;
+    let (mut public_ext, mut private_ext) = get_extensions(&pmsg.main_token_id, main_meta)?;
+    if !has_trait(&public_ext, &state.trait_names.alpha){
+        return Err(ContractError::NotAlpha { token_id: pmsg.main_token_id.to_string() });  
+    }
+
+    take_member_media(&pmsg.main_token_id, &mut public_ext, pack_members.len())?;
+    take_member_media(&pmsg.main_token_id, &mut private_ext, pack_members.len())?;
+
+    let xp_removed = if pmsg.keep_xp { 0 } else { members_xp(&state, &pack_members)? };
+    let levels = LEVEL_ITEM.load(deps.storage)?;
+    if let Some(new_lvl) = make_solo(&state, &levels, &pmsg.main_token_id, &mut public_ext, xp_removed)? {
+        response_attrs.push(("lvl_decrease".to_string(), new_lvl.to_string()));
+    }
+
+    remove_pack_members(deps.storage, &pmsg.main_token_id)?;
+    remove_pack_main(deps.storage, &pmsg.main_token_id)?;
+
+    response_msgs.push(set_metadata_msg(
+        pmsg.main_token_id.to_string(),
+        Some(Metadata {
+            token_uri: None,
+            extension: Some(public_ext),
+        }),
+        Some(Metadata {
+            token_uri: None,
+            extension: Some(private_ext),
+        }), 
+        None,
+        BLOCK_SIZE,
+        state.nft_contract.code_hash.clone(),
+        state.nft_contract.address.to_string()
+    )?);
+
+    //enter history record
+    let raw_address = deps.api.addr_canonicalize(from.as_str())?;
+    let history_store = HISTORY_STORE.add_suffix(raw_address.as_slice());
+    history_store.push(deps.storage, &HistoryToken {
+        wolf_main_token_id: pmsg.main_token_id.to_string(),
+        pack_member_token_ids: pack_members.iter().map(|x| x.token_id.to_string()).collect(),
+        pack_build_date: Some(_env.block.time.seconds()),
+        kind: HistoryKind::Disband
+    })?;
+
+    response_msgs.push(transfer_nft_msg(
+        from.to_string(),
+        pmsg.main_token_id.to_string(),
+        None,
+        None,
+        BLOCK_SIZE,
+        state.nft_contract.code_hash.to_string(),
+        state.nft_contract.address.to_string()
+    )?);
+    Ok(Response::new().add_messages(response_msgs).add_attributes(response_attrs))
 }
 
-fn get_querier(
-    deps: Deps,
-    permit: Permit,
-) -> StdResult<(CanonicalAddr, Option<CanonicalAddr>)> {
-    if let pmt = permit {
-        let me_raw: CanonicalAddr = MY_ADDRESS_ITEM.load(deps.storage)?;
-        let my_address = deps.api.addr_humanize(&me_raw)?;
-        let querier = deps.api.addr_canonicalize(&validate(
-            deps,
-            PREFIX_REVOKED_PERMITS,
-            &pmt,
-            my_address.to_string(),
-            None
-        )?)?;
-        if !pmt.check_permission(&secret_toolkit::permit::TokenPermissions::Owner) {
-            return Err(StdError::generic_err(format!(
-                "Owner permission is required for history queries, got permissions {:?}",
-                pmt.params.permissions
-            )));
-        }
-        return Ok((querier, Some(me_raw)));
-    }
-    return Err(StdError::generic_err(
-        "Unauthorized",
-    ));  
+pub fn rename_pack(
+    _env: Env,
+    deps: DepsMut,
+    sender: &Addr,
+    from: &Addr,
+    token_ids: Vec<String>, 
+    pmsg: PackRenameMsg
+) -> Result<Response, ContractError> {
+    let mut response_msgs: Vec<CosmosMsg> = Vec::new();
+    let state = CONFIG_ITEM.load(deps.storage)?;
+
+    if sender != &state.nft_contract.address{
+        return Err(ContractError::InvalidNftContract { address: sender.to_string() });
+    }
+    if !token_ids.contains(&pmsg.main_token_id) {
+        return Err(ContractError::MainTokenNotInList { token_id: pmsg.main_token_id.to_string() });  
+    }
+    if let Some(token_id) = token_ids.iter().find(|x| x != &&pmsg.main_token_id) {
+        return Err(ContractError::UnexpectedToken { token_id: token_id.to_string() });
+    }
+    let name = validate_pack_name(deps.storage, &pmsg.name)?;
+
+    let mut pack_main = PACK_MAIN_STORE.get(deps.storage, &pmsg.main_token_id)
+    .ok_or_else(|| ContractError::NoPack { token_id: pmsg.main_token_id.to_string() })?;
+
+    if state.is_rename_payment_needed {
+        let raw_address = deps.api.addr_canonicalize(from.as_str())?;
+        response_msgs.extend(pay_with_build_credit(deps.storage, &state, &raw_address, CreditKind::Rename)?);
+    }
+
+    let viewer = Some(ViewerInfo {
+        address: _env.contract.address.to_string(),
+        viewing_key: state.viewing_key.as_ref().unwrap().to_string(),
+    });
+    let main_meta: NftDossier =  nft_dossier_query(
+        deps.querier,
+        pmsg.main_token_id.to_string(),
+        viewer,
+        None,
+        BLOCK_SIZE,
+        state.nft_contract.code_hash.clone(),
+        state.nft_contract.address.to_string(),
+    )?;
+    check_holder(&pmsg.main_token_id, &main_meta, Some(&_env.contract.address))?;
+    let mut public_ext = get_extension(&pmsg.main_token_id, main_meta.public_metadata, "public_metadata")?;
+    if !has_trait(&public_ext, &state.trait_names.alpha){
+        return Err(ContractError::NotAlpha { token_id: pmsg.main_token_id.to_string() });  
+    }
+    public_ext.name = Some(name.to_string());
+    //brings over a pack rank recomputed while the alpha was away
+    set_trait(&mut public_ext, &state.trait_names.pack_rank, pack_main.pack_rank.to_string());
+
+    pack_main.name = name.to_string();
+    save_pack_main(deps.storage, &state, &pack_main)?;
+
+    //the name is only in the public metadata, private metadata is left as is
+    response_msgs.push(set_metadata_msg(
+        pmsg.main_token_id.to_string(),
+        Some(Metadata {
+            token_uri: None,
+            extension: Some(public_ext),
+        }),
+        None,
+        None,
+        BLOCK_SIZE,
+        state.nft_contract.code_hash.clone(),
+        state.nft_contract.address.to_string()
+    )?);
+    response_msgs.push(transfer_nft_msg(
+        from.to_string(),
+        pmsg.main_token_id.to_string(),
+        None,
+        None,
+        BLOCK_SIZE,
+        state.nft_contract.code_hash.to_string(),
+        state.nft_contract.address.to_string()
+    )?);
+    Ok(Response::new().add_messages(response_msgs).add_attribute("pack_name", name))
 }
 
+pub fn merge_packs(
+    _env: Env,
+    deps: DepsMut,
+    sender: &Addr,
+    from: &Addr,
+    token_ids: Vec<String>, 
+    pmsg: PackMergeMsg
+) -> Result<Response, ContractError> {
+    let mut response_msgs: Vec<CosmosMsg> = Vec::new();
+    let mut response_attrs = vec![];
+    let mut state = CONFIG_ITEM.load(deps.storage)?;
+    let levels = LEVEL_ITEM.load(deps.storage)?;
+
+    if sender != &state.nft_contract.address{
+        return Err(ContractError::InvalidNftContract { address: sender.to_string() });
+    }
+    if pmsg.main_token_id == pmsg.absorbed_token_id {
+        return Err(ContractError::SameAlpha { token_id: pmsg.main_token_id.to_string() });
+    }
+    for token_id in [&pmsg.main_token_id, &pmsg.absorbed_token_id] {
+        if !token_ids.contains(token_id) {
+            return Err(ContractError::TokenNotInList { token_id: token_id.to_string() });
+        }
+    }
+    if let Some(token_id) = token_ids.iter().find(|x| x != &&pmsg.main_token_id && x != &&pmsg.absorbed_token_id) {
+        return Err(ContractError::UnexpectedToken { token_id: token_id.to_string() });
+    }
+
+    let mut pack_members = PACK_MEMBER_STORE.get(deps.storage, &pmsg.main_token_id).unwrap_or_default();
+    let absorbed_members = PACK_MEMBER_STORE.get(deps.storage, &pmsg.absorbed_token_id).unwrap_or_default();
+    if absorbed_members.is_empty() {
+        return Err(ContractError::NoPack { token_id: pmsg.absorbed_token_id.to_string() });  
+    }
+    let slots_left = state.pack_max.saturating_sub(pack_members.len() as u16);
+    if absorbed_members.len() > slots_left as usize {
+        return Err(ContractError::PackFull { main_token_id: pmsg.main_token_id.to_string(), slots_left: slots_left });  
+    }
+
+    let viewer = Some(ViewerInfo {
+        address: _env.contract.address.to_string(),
+        viewing_key: state.viewing_key.as_ref().unwrap().to_string(),
+    });
+    let mut exts: Vec<(Extension, Extension)> = Vec::new();
+    for token_id in [&pmsg.main_token_id, &pmsg.absorbed_token_id] {
+        let meta: NftDossier =  nft_dossier_query(
+            deps.querier,
+            token_id.to_string(),
+            viewer.clone(),
+            None,
+            BLOCK_SIZE,
+            state.nft_contract.code_hash.clone(),
+            state.nft_contract.address.to_string(),
+        )?;
+        check_holder(token_id, &meta, Some(&_env.contract.address))?;
+        let (public_ext, private_ext) = get_extensions(token_id, meta)?;
+        if !has_trait(&public_ext, &state.trait_names.alpha){
+            return Err(ContractError::NotAlpha { token_id: token_id.to_string() });  
+        }
+        exts.push((public_ext, private_ext));
+    }
+    let (mut absorbed_public_ext, mut absorbed_private_ext) = exts.pop().unwrap();
+    let (mut main_public_ext, mut main_private_ext) = exts.pop().unwrap();
+
+    //move the absorbed members' images after the main alpha's members
+    let public_media = take_member_media(&pmsg.absorbed_token_id, &mut absorbed_public_ext, absorbed_members.len())?;
+    let private_media = take_member_media(&pmsg.absorbed_token_id, &mut absorbed_private_ext, absorbed_members.len())?;
+    if main_public_ext.media.as_ref().map_or(0, |x| x.len()) < pack_members.len() {
+        return Err(ContractError::MediaOutOfSync { token_id: pmsg.main_token_id.to_string() });
+    }
+    main_public_ext.media.get_or_insert_with(Vec::new).extend(public_media);
+    main_private_ext.media.get_or_insert_with(Vec::new).extend(private_media);
+
+    //a burned alpha hands over all of its xp, a demoted one only what its members brought
+    let xp_moved: u32 = if pmsg.burn_absorbed {
+        parse_trait(&pmsg.absorbed_token_id, &absorbed_public_ext, &state.trait_names.xp)?
+    } else {
+        members_xp(&state, &absorbed_members)?
+    };
+
+    pack_members.extend(absorbed_members.iter().cloned());
+    let pack_rank = calculate_pack_rank(&state, &pack_members);
+    let main_xp: u32 = parse_trait(&pmsg.main_token_id, &main_public_ext, &state.trait_names.xp)?;
+    let current_xp = main_xp.checked_add(xp_moved)
+        .ok_or_else(|| ContractError::InvalidTrait { token_id: pmsg.main_token_id.to_string(), trait_type: state.trait_names.xp.to_string(), value: main_xp.to_string() })?;
+    let current_lvl: u16 = parse_trait(&pmsg.main_token_id, &main_public_ext, &state.trait_names.level)?;
+    let new_lvl = if current_lvl < state.level_cap {
+            level_for_xp(&levels, current_xp)
+        } 
+        else { 
+            current_lvl 
+        }; 
+    if new_lvl > current_lvl {
+        response_attrs.push(("lvl_increase".to_string(), new_lvl.to_string()));
+    }
+    set_trait(&mut main_public_ext, &state.trait_names.xp, current_xp.to_string());
+    set_trait(&mut main_public_ext, &state.trait_names.level, new_lvl.to_string());
+    set_trait(&mut main_public_ext, &state.trait_names.pack, pack_members.len().to_string());
+    set_trait(&mut main_public_ext, &state.trait_names.pack_rank, pack_rank.to_string());
+
+    //the absorbed members are moved first so removing the absorbed pack leaves them in place
+    save_pack_members(deps.storage, &pmsg.main_token_id, &pack_members)?;
+    remove_pack_members(deps.storage, &pmsg.absorbed_token_id)?;
+    let mut pack_main = PACK_MAIN_STORE.get(deps.storage, &pmsg.main_token_id)
+    .unwrap_or(PackMain{
+        token_id: pmsg.main_token_id.to_string(),
+        pack_rank: 0,
+        pack_count: 0,
+        name: main_public_ext.name.clone().unwrap_or_default()
+    });
+    pack_main.pack_rank = pack_rank;
+    pack_main.pack_count = pack_members.len() as u16;
+    save_pack_main(deps.storage, &state, &pack_main)?;
+    remove_pack_main(deps.storage, &pmsg.absorbed_token_id)?;
+
+    response_msgs.push(set_metadata_msg(
+        pmsg.main_token_id.to_string(),
+        Some(Metadata {
+            token_uri: None,
+            extension: Some(main_public_ext),
+        }),
+        Some(Metadata {
+            token_uri: None,
+            extension: Some(main_private_ext),
+        }), 
+        None,
+        BLOCK_SIZE,
+        state.nft_contract.code_hash.clone(),
+        state.nft_contract.address.to_string()
+    )?);
+
+    let mut returned_token_ids = vec![pmsg.main_token_id.to_string()];
+    if pmsg.burn_absorbed {
+        response_msgs.push(batch_burn_nft_msg(
+            vec![Burn{ 
+                token_ids: vec![pmsg.absorbed_token_id.to_string()],
+                memo: None
+            }],
+            None,
+            BLOCK_SIZE,
+            state.nft_contract.code_hash.clone(),
+            state.nft_contract.address.to_string(),
+        )?);
+        state.total_burned = state.total_burned + 1;
+        CONFIG_ITEM.save(deps.storage, &state)?; 
+    } else {
+        make_solo(&state, &levels, &pmsg.absorbed_token_id, &mut absorbed_public_ext, xp_moved)?;
+        response_msgs.push(set_metadata_msg(
+            pmsg.absorbed_token_id.to_string(),
+            Some(Metadata {
+                token_uri: None,
+                extension: Some(absorbed_public_ext),
+            }),
+            Some(Metadata {
+                token_uri: None,
+                extension: Some(absorbed_private_ext),
+            }), 
+            None,
+            BLOCK_SIZE,
+            state.nft_contract.code_hash.clone(),
+            state.nft_contract.address.to_string()
+        )?);
+        returned_token_ids.push(pmsg.absorbed_token_id.to_string());
+    }
+
+    //enter history record
+    let raw_address = deps.api.addr_canonicalize(from.as_str())?;
+    let history_store = HISTORY_STORE.add_suffix(raw_address.as_slice());
+    history_store.push(deps.storage, &HistoryToken {
+        wolf_main_token_id: pmsg.main_token_id.to_string(),
+        pack_member_token_ids: absorbed_members.iter().map(|x| x.token_id.to_string()).collect(),
+        pack_build_date: Some(_env.block.time.seconds()),
+        kind: HistoryKind::Merge
+    })?;
+
+    response_msgs.push(batch_transfer_nft_msg(
+        vec![Transfer {
+            recipient: from.to_string(),
+            token_ids: returned_token_ids,
+            memo: None
+        }],
+        None,
+        BLOCK_SIZE,
+        state.nft_contract.code_hash.to_string(),
+        state.nft_contract.address.to_string()
+    )?);
+    Ok(Response::new().add_messages(response_msgs).add_attributes(response_attrs))
+}
+
+// XP the members had when they joined, which was added to their Alpha
+fn members_xp(
+    state: &State,
+    pack_members: &[PackMember]
+) -> Result<u32, ContractError> {
+    let mut xp: u32 = 0;
+    for member in pack_members.iter() {
+        let member_xp: u32 = parse_attribute(&member.token_id, &member.attributes, &state.trait_names.xp)?;
+        xp = xp.checked_add(member_xp)
+            .ok_or_else(|| ContractError::InvalidTrait { token_id: member.token_id.to_string(), trait_type: state.trait_names.xp.to_string(), value: member_xp.to_string() })?;
+    }
+    Ok(xp)
+}
+
+// Resets the pack traits of an Alpha whose members were taken away and removes the
+// given xp, returns the new level if it dropped
+fn make_solo(
+    state: &State,
+    levels: &[Level],
+    token_id: &str,
+    public_ext: &mut Extension,
+    xp_removed: u32
+) -> Result<Option<u16>, ContractError> {
+    set_trait(public_ext, &state.trait_names.pack, "0".to_string());
+    set_trait(public_ext, &state.trait_names.pack_rank, "0".to_string());
+    if xp_removed == 0 {
+        return Ok(None);
+    }
+    let current_xp: u32 = parse_trait(token_id, public_ext, &state.trait_names.xp)?;
+    let current_lvl: u16 = parse_trait(token_id, public_ext, &state.trait_names.level)?;
+    let new_xp = current_xp.saturating_sub(xp_removed);
+    let new_lvl = level_for_xp(levels, new_xp).min(current_lvl);
+    set_trait(public_ext, &state.trait_names.xp, new_xp.to_string());
+    set_trait(public_ext, &state.trait_names.level, new_lvl.to_string());
+    Ok(if new_lvl < current_lvl { Some(new_lvl) } else { None })
+}
+
+fn calculate_pack_rank(
+    state: &State,
+    pack_members: &[PackMember]
+) -> u32 {
+    //scores are set by the owner with no upper limit, so a pack rank tops out instead of overflowing
+    pack_members.iter().fold(0u32, |total, x| total.saturating_add(member_score(state, x)))
+}
+
+fn remove_pack_main(
+    storage: &mut dyn Storage,
+    token_id: &str
+) -> StdResult<()> {
+    if let Some(pack) = PACK_MAIN_STORE.get(storage, &token_id.to_string()) {
+        remove_pack_name(storage, &pack)?;
+        unindex_pack(storage, &LeaderboardOrder::PackRank, token_id, pack.pack_rank)?;
+        unindex_pack(storage, &LeaderboardOrder::PackCount, token_id, pack.pack_count as u32)?;
+    }
+    PACK_MAIN_STORE.remove(storage, &token_id.to_string())?;
+    Ok(())
+}
+
+fn member_score(
+    state: &State,
+    member: &PackMember
+) -> u32 {
+    let rule = &state.pack_rank_rule;
+    let base = match &rule.base {
+        RankScore::LinearInverse => state.collection_size.saturating_sub(member.rank) as u32,
+        RankScore::Tiered { tiers } => tiers.iter().find(|x| member.rank <= x.max_rank).map_or(0, |x| x.score),
+        RankScore::Flat { value } => *value
+    };
+    let bonus: u32 = rule.trait_bonuses.iter()
+        .filter(|bonus| member.attributes.iter().any(|x| x.trait_type.as_deref() == Some(bonus.trait_type.as_str()) && x.value == bonus.value))
+        .fold(0u32, |total, bonus| total.saturating_add(bonus.bonus));
+    base.saturating_add(bonus)
+}
+
+// Saves a pack and keeps the leaderboard and name indices in step with it
+fn save_pack_main(
+    storage: &mut dyn Storage,
+    state: &State,
+    pack: &PackMain
+) -> Result<(), ContractError> {
+    let old = PACK_MAIN_STORE.get(storage, &pack.token_id);
+    let renamed = match &old {
+        Some(old) => normalize_pack_name(&old.name) != normalize_pack_name(&pack.name),
+        None => true
+    };
+    if renamed {
+        if let Some(old) = &old {
+            remove_pack_name(storage, old)?;
+        }
+    }
+    let key = normalize_pack_name(&pack.name);
+    if !key.is_empty() {
+        match PACK_NAME_STORE.get(storage, &key) {
+            Some(token_id) if token_id == pack.token_id => {},
+            //names that were already shared before unique names were turned on stay as they are
+            Some(_) if !renamed => {},
+            Some(_) if state.unique_pack_names => {
+                return Err(ContractError::PackNameTaken { name: pack.name.to_string() });
+            },
+            _ => PACK_NAME_STORE.insert(storage, &key, &pack.token_id)?
+        }
+    }
+
+    PACK_MAIN_STORE.insert(storage, &pack.token_id, pack)?;
+    let old_rank = old.as_ref().map(|x| x.pack_rank);
+    if old_rank != Some(pack.pack_rank) {
+        index_pack(storage, &LeaderboardOrder::PackRank, &pack.token_id, old_rank, pack.pack_rank)?;
+    }
+    let old_count = old.as_ref().map(|x| x.pack_count as u32);
+    if old_count != Some(pack.pack_count as u32) {
+        index_pack(storage, &LeaderboardOrder::PackCount, &pack.token_id, old_count, pack.pack_count as u32)?;
+    }
+    Ok(())
+}
+
+// Saves the members of a pack and points each of them to it
+fn save_pack_members(
+    storage: &mut dyn Storage,
+    main_token_id: &str,
+    pack_members: &[PackMember]
+) -> StdResult<()> {
+    PACK_MEMBER_STORE.insert(storage, &main_token_id.to_string(), &pack_members.to_vec())?;
+    for member in pack_members.iter() {
+        MEMBER_PACK_STORE.insert(storage, &member.token_id, &main_token_id.to_string())?;
+    }
+    Ok(())
+}
+
+fn remove_pack_members(
+    storage: &mut dyn Storage,
+    main_token_id: &str
+) -> StdResult<()> {
+    let pack_members = PACK_MEMBER_STORE.get(storage, &main_token_id.to_string()).unwrap_or_default();
+    for member in pack_members.iter() {
+        //members that moved to another pack already point to it
+        if MEMBER_PACK_STORE.get(storage, &member.token_id).as_deref() == Some(main_token_id) {
+            MEMBER_PACK_STORE.remove(storage, &member.token_id)?;
+        }
+    }
+    PACK_MEMBER_STORE.remove(storage, &main_token_id.to_string())
+}
+
+fn remove_pack_name(
+    storage: &mut dyn Storage,
+    pack: &PackMain
+) -> StdResult<()> {
+    let key = normalize_pack_name(&pack.name);
+    //another pack may hold the name when names aren't unique
+    if PACK_NAME_STORE.get(storage, &key).as_ref() == Some(&pack.token_id) {
+        PACK_NAME_STORE.remove(storage, &key)?;
+    }
+    Ok(())
+}
+
+fn leaderboard_index(
+    order: &LeaderboardOrder
+) -> (&'static Item<'static, Vec<u32>>, &'static Keymap<'static, String, ()>) {
+    match order {
+        LeaderboardOrder::PackRank => (&PACK_RANK_SCORES_ITEM, &PACK_RANK_INDEX_STORE),
+        LeaderboardOrder::PackCount => (&PACK_COUNT_SCORES_ITEM, &PACK_COUNT_INDEX_STORE)
+    }
+}
+
+// Moves a pack from its old score to its new one, scores only rewrite the score list when they
+// gain their first pack or lose their last
+fn index_pack(
+    storage: &mut dyn Storage,
+    order: &LeaderboardOrder,
+    token_id: &str,
+    old_score: Option<u32>,
+    score: u32
+) -> StdResult<()> {
+    if let Some(old_score) = old_score {
+        unindex_pack(storage, order, token_id, old_score)?;
+    }
+    let (scores_item, index_store) = leaderboard_index(order);
+    let packs = index_store.add_suffix(&score.to_be_bytes());
+    if packs.get_len(storage)? == 0 {
+        let mut scores = scores_item.may_load(storage)?.unwrap_or_default();
+        //highest score first
+        if let Err(pos) = scores.binary_search_by(|x| score.cmp(x)) {
+            scores.insert(pos, score);
+            scores_item.save(storage, &scores)?;
+        }
+    }
+    packs.insert(storage, &token_id.to_string(), &())
+}
+
+fn unindex_pack(
+    storage: &mut dyn Storage,
+    order: &LeaderboardOrder,
+    token_id: &str,
+    score: u32
+) -> StdResult<()> {
+    let (scores_item, index_store) = leaderboard_index(order);
+    let packs = index_store.add_suffix(&score.to_be_bytes());
+    //packs saved before the indices existed aren't in them
+    if packs.get(storage, &token_id.to_string()).is_none() {
+        return Ok(());
+    }
+    packs.remove(storage, &token_id.to_string())?;
+    if packs.get_len(storage)? == 0 {
+        let mut scores = scores_item.may_load(storage)?.unwrap_or_default();
+        scores.retain(|x| *x != score);
+        scores_item.save(storage, &scores)?;
+    }
+    Ok(())
+}
+
+fn get_extensions(
+    token_id: &str,
+    meta: NftDossier
+) -> Result<(Extension, Extension), ContractError> {
+    let public_ext = get_extension(token_id, meta.public_metadata, "public_metadata")?;
+    let private_ext = get_extension(token_id, meta.private_metadata, "private_metadata")?;
+    Ok((public_ext, private_ext))
+}
+
+// Private metadata is only needed for the media a build moves, which a preview doesn't do
+fn get_plan_extensions(
+    token_id: &str,
+    meta: NftDossier,
+    preview: bool
+) -> Result<(Extension, Option<Extension>), ContractError> {
+    if !preview {
+        let (public_ext, private_ext) = get_extensions(token_id, meta)?;
+        return Ok((public_ext, Some(private_ext)));
+    }
+    let public_ext = get_extension(token_id, meta.public_metadata, "public_metadata")?;
+    Ok((public_ext, meta.private_metadata.and_then(|x| x.extension)))
+}
+
+fn get_extension(
+    token_id: &str,
+    metadata: Option<Metadata>,
+    field: &str
+) -> Result<Extension, ContractError> {
+    metadata
+        .ok_or_else(|| ContractError::MissingMetadata { token_id: token_id.to_string(), field: field.to_string() })?
+        .extension
+        .ok_or_else(|| ContractError::MissingMetadata { token_id: token_id.to_string(), field: format!("{}.extension", field) })
+}
+
+fn get_attributes<'a>(
+    token_id: &str,
+    ext: &'a Extension
+) -> Result<&'a Vec<Trait>, ContractError> {
+    ext.attributes.as_ref()
+        .ok_or_else(|| ContractError::MissingMetadata { token_id: token_id.to_string(), field: "public_metadata.extension.attributes".to_string() })
+}
+
+fn parse_trait<T: FromStr>(
+    token_id: &str,
+    ext: &Extension,
+    trait_type: &str
+) -> Result<T, ContractError> {
+    parse_attribute(token_id, get_attributes(token_id, ext)?, trait_type)
+}
+
+fn parse_attribute<T: FromStr>(
+    token_id: &str,
+    attributes: &[Trait],
+    trait_type: &str
+) -> Result<T, ContractError> {
+    let attr = attributes.iter()
+        .find(|x| x.trait_type.as_deref() == Some(trait_type))
+        .ok_or_else(|| ContractError::MissingTrait { token_id: token_id.to_string(), trait_type: trait_type.to_string() })?;
+    attr.value.trim().parse::<T>()
+        .map_err(|_| ContractError::InvalidTrait { token_id: token_id.to_string(), trait_type: trait_type.to_string(), value: attr.value.to_string() })
+}
+
+fn first_media(
+    token_id: &str,
+    ext: &Extension,
+    metadata_type: &str
+) -> Result<MediaFile, ContractError> {
+    ext.media.as_ref()
+        .and_then(|x| x.first())
+        .cloned()
+        .ok_or_else(|| ContractError::MissingMedia { token_id: token_id.to_string(), metadata_type: metadata_type.to_string() })
+}
+
+fn has_trait(
+    ext: &Extension,
+    trait_type: &str
+) -> bool {
+    ext.attributes.as_ref().map_or(false, |attrs| attrs.iter().any(|x| x.trait_type.as_deref() == Some(trait_type)))
+}
+
+fn set_trait(
+    ext: &mut Extension,
+    trait_type: &str,
+    value: String
+) {
+    let attributes = ext.attributes.get_or_insert_with(Vec::new);
+    match attributes.iter_mut().find(|x| x.trait_type.as_deref() == Some(trait_type)) {
+        Some(attr) => attr.value = value,
+        None => attributes.push(Trait{
+            trait_type: Some(trait_type.to_string()),
+            value: value,
+            display_type: None,
+            max_value: None
+        })
+    }
+}
+
+// Member images are appended after the alpha's own media in the same order as
+// the pack member list, so a member's image sits at the same distance from the
+// end of the media list as the member does from the end of the pack.
+fn remove_member_media(
+    token_id: &str,
+    ext: &mut Extension,
+    pack_size: usize,
+    member_index: usize
+) -> Result<MediaFile, ContractError> {
+    let media = ext.media.get_or_insert_with(Vec::new);
+    if media.len() < pack_size {
+        return Err(ContractError::MediaOutOfSync { token_id: token_id.to_string() });
+    }
+    let offset = media.len() - pack_size;
+    Ok(media.remove(offset + member_index))
+}
+
+fn take_member_media(
+    token_id: &str,
+    ext: &mut Extension,
+    pack_size: usize
+) -> Result<Vec<MediaFile>, ContractError> {
+    let media = ext.media.get_or_insert_with(Vec::new);
+    if media.len() < pack_size {
+        return Err(ContractError::MediaOutOfSync { token_id: token_id.to_string() });
+    }
+    Ok(media.split_off(media.len() - pack_size))
+}
+
+fn add_member_media(
+    token_id: &str,
+    ext: &mut Extension,
+    pack_size: usize,
+    media_file: MediaFile
+) -> Result<(), ContractError> {
+    let media = ext.media.get_or_insert_with(Vec::new);
+    if media.len() < pack_size {
+        return Err(ContractError::MediaOutOfSync { token_id: token_id.to_string() });
+    }
+    media.push(media_file);
+    Ok(())
+}
+
+pub fn try_claim_back(
+    deps: DepsMut,
+    _env: Env,
+    sender: &Addr,
+    token_ids: Option<Vec<String>>
+) -> Result<Response, ContractError> {  
+    claim_back(deps, sender, token_ids)
+}
+
+pub fn try_admin_claim_back(
+    deps: DepsMut,
+    _env: Env,
+    sender: &Addr,
+    owner: Addr,
+    token_ids: Option<Vec<String>>
+) -> Result<Response, ContractError> {  
+    let state = CONFIG_ITEM.load(deps.storage)?;
+
+    if sender.clone() != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }  
+
+    claim_back(deps, &owner, token_ids)
+}
+
+fn claim_back(
+    deps: DepsMut,
+    owner: &Addr,
+    token_ids: Option<Vec<String>>
+) -> Result<Response, ContractError> {  
+    let state = CONFIG_ITEM.load(deps.storage)?;
+    let owner_raw = deps.api.addr_canonicalize(&owner.to_string())?;
+    let mut holding: Vec<String> = INHOLDING_NFT_STORE.get(deps.storage, &owner_raw).ok_or(ContractError::NothingInHolding {})?;
+
+    //claim everything in holding unless a subset was requested
+    let claimed: Vec<String> = match token_ids {
+        Some(ids) => {
+            if ids.is_empty() {
+                return Err(ContractError::EmptyTokenList {});
+            }
+            for id in ids.iter() {
+                let pos = holding.iter().position(|x| x == id)
+                    .ok_or_else(|| ContractError::NotInHolding { token_id: id.to_string() })?;
+                holding.remove(pos);
+            }
+            ids
+        },
+        None => holding.drain(..).collect()
+    };
+
+    if holding.is_empty() {
+        INHOLDING_NFT_STORE.remove(deps.storage, &owner_raw)?;
+    }
+    else {
+        INHOLDING_NFT_STORE.insert(deps.storage, &owner_raw, &holding)?;
+    }
+
+    Ok(Response::new()
+        .add_message(batch_transfer_nft_msg(
+            vec![Transfer{
+                recipient: owner.to_string(),
+                token_ids: claimed,
+                memo: None
+            }],
+            None,
+            BLOCK_SIZE,
+            state.nft_contract.code_hash.clone(),
+            state.nft_contract.address.to_string(),
+        )?)
+    )
+}
+
+// Uses up a credit of the given kind and releases its escrowed payment to the receiving address
+fn pay_with_build_credit(
+    storage: &mut dyn Storage,
+    state: &State,
+    raw_address: &CanonicalAddr,
+    kind: CreditKind
+) -> Result<Option<CosmosMsg>, ContractError> {
+    let (payment_contract, price) = use_build_credit(storage, raw_address, kind)?;
+    //builds paid for before the escrow have already been sent on
+    let payment_contract = match payment_contract {
+        Some(payment_contract) if !price.is_zero() => payment_contract,
+        _ => return Ok(None)
+    };
+    Ok(Some(transfer_msg(
+        state.receiving_address.to_string(),
+        price,
+        None,
+        None,
+        BLOCK_SIZE,
+        payment_code_hash(state, &payment_contract),
+        payment_contract.address.to_string(),
+    )?))
+}
+
+// Code hash of a payment contract as it is configured now, credits keep the hash
+// from when they were paid which is stale after an UpdatePayment
+fn payment_code_hash(
+    state: &State,
+    payment_contract: &ContractInfo
+) -> String {
+    state.valid_payments.as_ref()
+        .and_then(|x| x.iter().find(|x| x.address == payment_contract.address))
+        .map_or_else(|| payment_contract.code_hash.to_string(), |x| x.code_hash.to_string())
+}
+
+fn use_build_credit(
+    storage: &mut dyn Storage,
+    raw_address: &CanonicalAddr,
+    kind: CreditKind
+) -> Result<(Option<ContractInfo>, Uint128), ContractError> {
+    let mut credits = BUILD_CREDIT_STORE.get(storage, raw_address).unwrap_or_else(Vec::new);
+    //oldest payments are used first
+    let index = credits.iter().position(|x| x.kind == kind)
+        .ok_or(ContractError::PaymentNotReceived {})?;
+
+    let credit = &mut credits[index];
+    let used = (credit.payment_contract.clone(), credit.price);
+    credit.quantity = credit.quantity - 1;
+    if credit.quantity == 0 {
+        credits.remove(index);
+    }
+
+    if credits.is_empty() {
+        BUILD_CREDIT_STORE.remove(storage, raw_address)?;
+    }
+    else {
+        BUILD_CREDIT_STORE.insert(storage, raw_address, &credits)?;
+    }
+    Ok(used)
+}
+
+pub fn try_refund_credits(
+    deps: DepsMut,
+    _env: Env,
+    sender: &Addr
+) -> Result<Response, ContractError> {  
+    refund_credits(deps, sender)
+}
+
+pub fn try_admin_refund_credits(
+    deps: DepsMut,
+    _env: Env,
+    sender: &Addr,
+    address: Addr
+) -> Result<Response, ContractError> {  
+    let state = CONFIG_ITEM.load(deps.storage)?;
+
+    if sender.clone() != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }  
+
+    refund_credits(deps, &address)
+}
+
+fn refund_credits(
+    deps: DepsMut,
+    address: &Addr
+) -> Result<Response, ContractError> {  
+    let state = CONFIG_ITEM.load(deps.storage)?;
+    let raw_address = deps.api.addr_canonicalize(&address.to_string())?;
+    //credits from before the escrow hold nothing to pay back, so they are kept
+    let (credits, prepaid): (Vec<BuildCredit>, Vec<BuildCredit>) = BUILD_CREDIT_STORE.get(deps.storage, &raw_address)
+        .unwrap_or_else(Vec::new)
+        .into_iter()
+        .partition(|x| x.payment_contract.is_some() && !x.price.is_zero());
+    if credits.is_empty() {
+        return Err(ContractError::NoBuildCredits {});  
+    }
+    if prepaid.is_empty() {
+        BUILD_CREDIT_STORE.remove(deps.storage, &raw_address)?;
+    }
+    else {
+        BUILD_CREDIT_STORE.insert(deps.storage, &raw_address, &prepaid)?;
+    }
+
+    //pay back each credit in the token it was paid with
+    let mut response_msgs: Vec<CosmosMsg> = Vec::new();
+    for credit in credits.iter() {
+        let payment_contract = match &credit.payment_contract {
+            Some(payment_contract) => payment_contract,
+            None => continue
+        };
+        response_msgs.push(transfer_msg(
+            address.to_string(),
+            credit.price * Uint128::from(credit.quantity),
+            None,
+            None,
+            BLOCK_SIZE,
+            payment_code_hash(&state, payment_contract),
+            payment_contract.address.to_string(),
+        )?);
+    }
+
+    Ok(Response::new().add_messages(response_msgs))
+}
+
+pub fn try_send_nft_back(
+    deps: DepsMut,
+    _env: Env,
+    sender: &Addr,
+    token_id: String,
+    owner: Addr
+) -> Result<Response, ContractError> { 
+    let state = CONFIG_ITEM.load(deps.storage)?;
+
+    if sender.clone() != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }  
+ 
+    Ok(Response::new()
+        .add_message(transfer_nft_msg(
+            owner.to_string(),
+            token_id.to_string(),
+            None,
+            None,
+            BLOCK_SIZE,
+            state.nft_contract.code_hash.to_string(),
+            state.nft_contract.address.to_string()
+        )?)
+    )
+}
+
+fn try_revoke_permit(
+    deps: DepsMut,
+    sender: &Addr,
+    permit_name: &str,
+) -> Result<Response, ContractError> {
+    RevokedPermits::revoke_permit(deps.storage, PREFIX_REVOKED_PERMITS, &sender.to_string(), permit_name);
+    
+    Ok(Response::default())
+}
+
+fn try_create_viewing_key(
+    deps: DepsMut,
+    env: Env,
+    info: &MessageInfo,
+    entropy: String
+) -> Result<Response, ContractError> {
+    let key = ViewingKey::create(deps.storage, info, &env, info.sender.as_str(), entropy.as_bytes());
+    Ok(Response::new().set_data(to_binary(&ViewingKeyResponse { key })?))
+}
+
+fn try_set_viewing_key(
+    deps: DepsMut,
+    sender: &Addr,
+    key: String
+) -> Result<Response, ContractError> {
+    ViewingKey::set(deps.storage, sender.as_str(), &key);
+    Ok(Response::new().set_data(to_binary(&ViewingKeyResponse { key })?))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn try_update_config(
+    deps: DepsMut,
+    _env: Env,
+    sender: &Addr,
+    receiving_address: Option<Addr>,
+    pack_max: Option<u16>,
+    collection_size: Option<u16>,
+    level_cap: Option<u16>,
+    is_payment_needed: Option<bool>,
+    nft_contract: Option<ContractInfo>,
+    member_requirement: Option<MemberRequirement>,
+    trait_names: Option<TraitNames>,
+    pack_rank_rule: Option<PackRankRule>,
+    is_rename_payment_needed: Option<bool>,
+    unique_pack_names: Option<bool>
+) -> Result<Response, ContractError> { 
+    let mut state = CONFIG_ITEM.load(deps.storage)?;
+    let mut response_msgs: Vec<CosmosMsg> = Vec::new();
+    let mut response_attrs = vec![];
+
+    if sender.clone() != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }  
+
+    if let Some(receiving_address) = receiving_address {
+        state.receiving_address = deps.api.addr_validate(receiving_address.as_str())?;
+        response_attrs.push(("receiving_address".to_string(), state.receiving_address.to_string()));
+    }
+
+    if let Some(pack_max) = pack_max {
+        if pack_max == 0 {
+            return Err(ContractError::InvalidPackMax {});
+        }
+        state.pack_max = pack_max;
+        response_attrs.push(("pack_max".to_string(), pack_max.to_string()));
+    }
+
+    //ranks are checked against the collection size as they are set, scanning every
+    //stored rank here would cost gas in proportion to the collection
+    if let Some(collection_size) = collection_size {
+        state.collection_size = collection_size;
+        response_attrs.push(("collection_size".to_string(), collection_size.to_string()));
+    }
+
+    if let Some(level_cap) = level_cap {
+        if level_cap == 0 {
+            return Err(ContractError::InvalidLevelCap {});
+        }
+        state.level_cap = level_cap;
+        response_attrs.push(("level_cap".to_string(), level_cap.to_string()));
+    }
+
+    if let Some(is_payment_needed) = is_payment_needed {
+        state.is_payment_needed = is_payment_needed;
+        response_attrs.push(("is_payment_needed".to_string(), is_payment_needed.to_string()));
+    }
+
+    if let Some(nft_contract) = nft_contract {
+        let nft_contract = ContractInfo{
+            code_hash: nft_contract.code_hash,
+            address: deps.api.addr_validate(nft_contract.address.as_str())?
+        };
+        //register with the new nft contract the same way instantiate does
+        response_msgs.push(register_receive_nft_msg(
+            _env.contract.code_hash.clone(),
+            Some(true),
+            None,
+            BLOCK_SIZE,
+            nft_contract.code_hash.clone(),
+            nft_contract.address.to_string(),
+        )?);
+        response_msgs.push(set_viewing_key_msg(
+            state.viewing_key.as_ref().unwrap().to_string(),
+            None,
+            BLOCK_SIZE,
+            nft_contract.code_hash.clone(),
+            nft_contract.address.to_string(),
+        )?);
+        response_attrs.push(("nft_contract".to_string(), nft_contract.address.to_string()));
+        state.nft_contract = nft_contract;
+    }
+
+    if let Some(member_requirement) = member_requirement {
+        state.member_requirement = member_requirement;
+        let levels = LEVEL_ITEM.load(deps.storage)?;
+        let required_xp = required_member_xp(&state, &levels)?;
+        response_attrs.push(("member_required_level".to_string(), level_for_xp(&levels, required_xp).to_string()));
+    }
+
+    if let Some(trait_names) = trait_names {
+        validate_trait_names(&trait_names)?;
+        response_attrs.push(("trait_names".to_string(), format!("{}, {}, {}, {}, {}", trait_names.alpha, trait_names.xp, trait_names.level, trait_names.pack, trait_names.pack_rank)));
+        state.trait_names = trait_names;
+    }
+
+    if let Some(pack_rank_rule) = pack_rank_rule {
+        validate_pack_rank_rule(&pack_rank_rule)?;
+        //stored packs keep their old rank until RecomputePackRanks is run
+        response_attrs.push(("pack_rank_rule".to_string(), "updated".to_string()));
+        state.pack_rank_rule = pack_rank_rule;
+    }
+
+    if let Some(is_rename_payment_needed) = is_rename_payment_needed {
+        state.is_rename_payment_needed = is_rename_payment_needed;
+        response_attrs.push(("is_rename_payment_needed".to_string(), is_rename_payment_needed.to_string()));
+    }
+
+    if let Some(unique_pack_names) = unique_pack_names {
+        //only names set from now on are checked, existing duplicates are kept
+        state.unique_pack_names = unique_pack_names;
+        response_attrs.push(("unique_pack_names".to_string(), unique_pack_names.to_string()));
+    }
+
+    CONFIG_ITEM.save(deps.storage, &state)?;
+    Ok(Response::new().add_messages(response_msgs).add_attributes(response_attrs))
+}
+
+fn try_set_name_blocklist(
+    deps: DepsMut,
+    sender: &Addr,
+    words: Vec<String>
+) -> Result<Response, ContractError> { 
+    let state = CONFIG_ITEM.load(deps.storage)?;
+
+    if sender.clone() != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }  
+
+    let words: Vec<String> = words.iter()
+        .map(|x| x.trim().to_lowercase())
+        .filter(|x| !x.is_empty())
+        .collect();
+    NAME_BLOCKLIST_ITEM.save(deps.storage, &words)?;
+    Ok(Response::new().add_attribute("blocked_words", words.len().to_string()))
+}
+
+// Key of a pack name in the name index, builds store names as they were sent
+fn normalize_pack_name(
+    name: &str
+) -> String {
+    name.trim().to_lowercase()
+}
+
+// Returns the trimmed name if it can be used for a pack
+fn validate_pack_name(
+    storage: &dyn Storage,
+    name: &str
+) -> Result<String, ContractError> {
+    let name = name.trim();
+    let length = name.chars().count();
+    if length < MIN_PACK_NAME_LENGTH || length > MAX_PACK_NAME_LENGTH {
+        return Err(ContractError::InvalidPackNameLength { min: MIN_PACK_NAME_LENGTH, max: MAX_PACK_NAME_LENGTH });
+    }
+    if let Some(character) = name.chars().find(|x| !(x.is_ascii_alphanumeric() || [' ', '-', '\'', '.'].contains(x))) {
+        return Err(ContractError::InvalidPackNameChar { character });
+    }
+    let lowercase = name.to_lowercase();
+    let blocklist = NAME_BLOCKLIST_ITEM.may_load(storage)?.unwrap_or_default();
+    if blocklist.iter().any(|x| lowercase.contains(x.as_str())) {
+        return Err(ContractError::BlockedPackName {});
+    }
+    Ok(name.to_string())
+}
+
+fn validate_pack_rank_rule(
+    rule: &PackRankRule
+) -> Result<(), ContractError> {
+    if let RankScore::Tiered { tiers } = &rule.base {
+        if tiers.is_empty() {
+            return Err(ContractError::EmptyRankTiers {});
+        }
+        for pair in tiers.windows(2) {
+            if pair[1].max_rank <= pair[0].max_rank {
+                return Err(ContractError::RankTiersNotIncreasing { name: pair[1].name.to_string() });
+            }
+        }
+    }
+    if rule.trait_bonuses.iter().any(|x| x.trait_type.trim().is_empty()) {
+        return Err(ContractError::EmptyTraitName {});
+    }
+    Ok(())
+}
+
+// Alphas the contract doesn't hold can't have their metadata changed, so only the stored
+// packs are updated here and the "Pack Rank" trait follows the next time the Alpha is sent in
+fn try_recompute_pack_ranks(
+    deps: DepsMut,
+    sender: &Addr,
+    start_page: u32,
+    page_size: u32
+) -> Result<Response, ContractError> { 
+    let state = CONFIG_ITEM.load(deps.storage)?;
+
+    if sender.clone() != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }  
+
+    let mut recomputed: u32 = 0;
+    let packs = PACK_MAIN_STORE.paging(deps.storage, start_page, page_size)?;
+    for (_, mut pack) in packs.into_iter() {
+        let mut pack_members = PACK_MEMBER_STORE.get(deps.storage, &pack.token_id).unwrap_or_default();
+        //members keep the rank they joined with, pick up any fixes made with SetRanks since
+        let mut ranks_changed = false;
+        for member in pack_members.iter_mut() {
+            if let Some(rank) = RANK_STORE.get(deps.storage, &member.token_id) {
+                if rank != member.rank {
+                    member.rank = rank;
+                    ranks_changed = true;
+                }
+            }
+        }
+        if ranks_changed {
+            PACK_MEMBER_STORE.insert(deps.storage, &pack.token_id, &pack_members)?;
+        }
+
+        let pack_rank = calculate_pack_rank(&state, &pack_members);
+        if pack_rank == pack.pack_rank {
+            continue;
+        }
+        pack.pack_rank = pack_rank;
+        save_pack_main(deps.storage, &state, &pack)?;
+        recomputed = recomputed + 1;
+    }
+
+    Ok(Response::new().add_attribute("packs_recomputed", recomputed.to_string()))
+}
+
+fn validate_trait_names(
+    trait_names: &TraitNames
+) -> Result<(), ContractError> {
+    let names = [&trait_names.alpha, &trait_names.xp, &trait_names.level, &trait_names.pack, &trait_names.pack_rank];
+    for (index, name) in names.iter().enumerate() {
+        if name.trim().is_empty() {
+            return Err(ContractError::EmptyTraitName {});
+        }
+        if names[..index].contains(name) {
+            return Err(ContractError::DuplicateTraitName { name: name.to_string() });
+        }
+    }
+    Ok(())
+}
+
+fn try_set_levels(
+    deps: DepsMut,
+    sender: &Addr,
+    levels: Vec<Level>
+) -> Result<Response, ContractError> { 
+    let state = CONFIG_ITEM.load(deps.storage)?;
+
+    if sender.clone() != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }  
+
+    validate_levels(&levels)?;
+    required_member_xp(&state, &levels)?;
+    LEVEL_ITEM.save(deps.storage, &levels)?;
+    Ok(Response::default())
+}
+
+fn try_add_levels(
+    deps: DepsMut,
+    sender: &Addr,
+    levels: Vec<Level>
+) -> Result<Response, ContractError> { 
+    let state = CONFIG_ITEM.load(deps.storage)?;
+
+    if sender.clone() != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }  
+
+    let mut current_levels = LEVEL_ITEM.load(deps.storage)?;
+    current_levels.extend(levels);
+    validate_levels(&current_levels)?;
+    LEVEL_ITEM.save(deps.storage, &current_levels)?;
+    Ok(Response::default())
+}
+
+fn try_remove_levels(
+    deps: DepsMut,
+    sender: &Addr,
+    from_level: u16
+) -> Result<Response, ContractError> { 
+    let state = CONFIG_ITEM.load(deps.storage)?;
+
+    if sender.clone() != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }  
+
+    //levels can only be removed from the top so the table stays contiguous
+    let mut levels = LEVEL_ITEM.load(deps.storage)?;
+    let position = levels.iter().position(|x| x.level == from_level)
+        .ok_or(ContractError::UnknownLevel { level: from_level })?;
+    levels.truncate(position);
+    validate_levels(&levels)?;
+    required_member_xp(&state, &levels)?;
+    LEVEL_ITEM.save(deps.storage, &levels)?;
+    Ok(Response::default())
+}
+
+fn validate_levels(
+    levels: &[Level]
+) -> Result<(), ContractError> {
+    if levels.is_empty() {
+        return Err(ContractError::EmptyLevels {});
+    }
+    for pair in levels.windows(2) {
+        if pair[1].level != pair[0].level + 1 {
+            return Err(ContractError::LevelNotContiguous { level: pair[1].level, previous: pair[0].level });
+        }
+        if pair[1].xp_needed <= pair[0].xp_needed {
+            return Err(ContractError::LevelXpNotIncreasing { level: pair[1].level, previous: pair[0].level });
+        }
+    }
+    Ok(())
+}
+
+fn required_member_xp(
+    state: &State,
+    levels: &[Level]
+) -> Result<u32, ContractError> {
+    match &state.member_requirement {
+        MemberRequirement::MinXp { xp } => Ok(*xp),
+        MemberRequirement::MinLevel { level } => levels.iter().find(|x| &x.level == level)
+            .map(|x| x.xp_needed)
+            .ok_or(ContractError::UnknownLevel { level: *level })
+    }
+}
+
+// Level reached with the given xp, xp past the last level stays at the last level
+fn level_for_xp(
+    levels: &[Level],
+    xp: u32
+) -> u16 {
+    match levels.iter().find(|x| x.xp_needed > xp) {
+        Some(next_level) => next_level.level.saturating_sub(1),
+        None => levels.last().map_or(0, |x| x.level)
+    }
+}
+
+fn try_set_ranks(
+    deps: DepsMut,
+    sender: &Addr,
+    ranks: Vec<Rank>
+) -> Result<Response, ContractError> { 
+    let state = CONFIG_ITEM.load(deps.storage)?;
+
+    if sender.clone() != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }  
+
+    if ranks.len() > MAX_RANK_BATCH {
+        return Err(ContractError::RankBatchTooLarge { max: MAX_RANK_BATCH as u32 });
+    }
+
+    for rank in ranks.iter() {
+        validate_rank(&state, rank)?;
+        RANK_STORE.insert(deps.storage, &rank.token_id, &rank.rank)?;
+    }
+    Ok(Response::new().add_attribute("ranks_set", ranks.len().to_string()))
+}
+
+fn try_remove_ranks(
+    deps: DepsMut,
+    sender: &Addr,
+    token_ids: Vec<String>
+) -> Result<Response, ContractError> { 
+    let state = CONFIG_ITEM.load(deps.storage)?;
+
+    if sender.clone() != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }  
+
+    if token_ids.len() > MAX_RANK_BATCH {
+        return Err(ContractError::RankBatchTooLarge { max: MAX_RANK_BATCH as u32 });
+    }
+
+    for token_id in token_ids.iter() {
+        RANK_STORE.remove(deps.storage, token_id)?;
+    }
+    Ok(Response::new().add_attribute("ranks_removed", token_ids.len().to_string()))
+}
+
+// Moves history in batches for builders that didn't fit in the migrate message
+fn try_migrate_history(
+    deps: DepsMut,
+    sender: &Addr,
+    addresses: Vec<Addr>
+) -> Result<Response, ContractError> { 
+    let state = CONFIG_ITEM.load(deps.storage)?;
+
+    if sender.clone() != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }  
+
+    let migrated = migrate_history(deps.storage, deps.api, &addresses)?;
+    Ok(Response::new().add_attribute("history_migrated", migrated.to_string()))
+}
+
+// History used to be written under the human address of the builder while it is
+// read under the canonical address, this moves those entries to the canonical key
+fn migrate_history(
+    storage: &mut dyn Storage,
+    api: &dyn Api,
+    addresses: &[Addr]
+) -> StdResult<u32> { 
+    let mut migrated: u32 = 0;
+    for address in addresses.iter() {
+        let old_store = LEGACY_HISTORY_STORE.add_suffix(address.as_str().as_bytes());
+        let old_len = old_store.get_len(storage)?;
+        if old_len == 0 {
+            continue;
+        }
+        let raw_address = api.addr_canonicalize(address.as_str())?;
+        let history_store = HISTORY_STORE.add_suffix(raw_address.as_slice());
+
+        //old entries didn't record a kind, they are all treated as builds
+        let mut history: Vec<HistoryToken> = old_store.iter(storage)?
+            .map(|x| x.map(|old| HistoryToken {
+                wolf_main_token_id: old.wolf_main_token_id,
+                pack_member_token_ids: old.pack_member_token_ids,
+                pack_build_date: old.pack_build_date,
+                kind: HistoryKind::Build
+            }))
+            .collect::<StdResult<Vec<HistoryToken>>>()?;
+        history.extend(history_store.iter(storage)?.collect::<StdResult<Vec<HistoryToken>>>()?);
+        //keep the entries in build order
+        history.sort_by_key(|x| x.pack_build_date);
+
+        for _ in 0..old_len {
+            old_store.pop(storage)?;
+        }
+        for _ in 0..history_store.get_len(storage)? {
+            history_store.pop(storage)?;
+        }
+        for history_token in history.iter() {
+            history_store.push(storage, history_token)?;
+        }
+        migrated = migrated + old_len;
+    }
+    Ok(migrated)
+}
+
+fn try_reindex_packs(
+    deps: DepsMut,
+    sender: &Addr,
+    start_page: u32,
+    page_size: u32
+) -> Result<Response, ContractError> { 
+    let state = CONFIG_ITEM.load(deps.storage)?;
+
+    if sender.clone() != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }  
+
+    //backfills the leaderboard, name and member indices for packs stored before they existed
+    let packs = PACK_MAIN_STORE.paging(deps.storage, start_page, page_size)?;
+    for (_, pack) in packs.iter() {
+        save_pack_main(deps.storage, &state, pack)?;
+        //save_pack_main skips unchanged scores, which is every pack here
+        index_pack(deps.storage, &LeaderboardOrder::PackRank, &pack.token_id, None, pack.pack_rank)?;
+        index_pack(deps.storage, &LeaderboardOrder::PackCount, &pack.token_id, None, pack.pack_count as u32)?;
+        let pack_members = PACK_MEMBER_STORE.get(deps.storage, &pack.token_id).unwrap_or_default();
+        save_pack_members(deps.storage, &pack.token_id, &pack_members)?;
+    }
+    Ok(Response::new().add_attribute("packs_indexed", packs.len().to_string()))
+}
+
+fn validate_rank(
+    state: &State,
+    rank: &Rank
+) -> Result<(), ContractError> {
+    if rank.rank == 0 || rank.rank > state.collection_size {
+        return Err(ContractError::RankOutOfRange { token_id: rank.token_id.to_string(), rank: rank.rank, collection_size: state.collection_size });
+    }
+    Ok(())
+}
+
+fn try_add_payment(
+    deps: DepsMut,
+    sender: &Addr,
+    payment: PaymentContractInfo
+) -> Result<Response, ContractError> { 
+    let mut state = CONFIG_ITEM.load(deps.storage)?;
+
+    if sender.clone() != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }  
+
+    if state.valid_payments.is_none() {
+        state.valid_payments = Some(vec![]);
+    }
+    let payment_contract = state.valid_payments.as_ref().unwrap().iter().find(|x| x.name == payment.name);
+    if payment_contract.is_some(){
+        return Err(ContractError::PaymentNameExists { name: payment.name.to_string() });  
+    }  
+    if state.valid_payments.as_ref().unwrap().iter().any(|x| x.address == payment.address){
+        return Err(ContractError::PaymentContractExists { address: payment.address.to_string() });  
+    }  
+
+    let set_viewing_key = set_viewing_key_msg(
+        state.viewing_key.as_ref().unwrap().to_string(),
+        None,
+        BLOCK_SIZE,
+        payment.code_hash.to_string(),
+        payment.address.to_string(),
+    )?;
+    state.valid_payments.as_mut().unwrap().push(payment);
+
+    CONFIG_ITEM.save(deps.storage, &state)?;
+    Ok(Response::new().add_message(set_viewing_key))
+}
+
+fn try_update_payment(
+    deps: DepsMut,
+    sender: &Addr,
+    payment_name: String,
+    payment_needed: Option<Uint128>,
+    rename_price: Option<Uint128>,
+    code_hash: Option<String>
+) -> Result<Response, ContractError> { 
+    let mut state = CONFIG_ITEM.load(deps.storage)?;
+
+    if sender.clone() != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }  
+
+    let payment_contract = state.valid_payments.as_mut().and_then(|x| x.iter_mut().find(|x| x.name == payment_name));
+    if let Some(payment_contract) = payment_contract {
+        if let Some(payment_needed) = payment_needed {
+            payment_contract.payment_needed = payment_needed;
+        }
+        if let Some(rename_price) = rename_price {
+            payment_contract.rename_price = rename_price;
+        }
+        if let Some(code_hash) = code_hash {
+            payment_contract.code_hash = code_hash;
+        }
+    }
+    else {
+        return Err(ContractError::UnknownPaymentName { name: payment_name });  
+    }
+
+    CONFIG_ITEM.save(deps.storage, &state)?;
+    Ok(Response::default())
+}
+
+fn try_remove_payment(
+    deps: DepsMut,
+    sender: &Addr,
+    payment_name: String,
+) -> Result<Response, ContractError> {
+    let mut state = CONFIG_ITEM.load(deps.storage)?;
+
+    if sender.clone() != state.owner {
+        return Err(ContractError::Unauthorized {});
+    }  
+
+    if state.valid_payments.is_none() {
+        return Err(ContractError::UnknownPaymentName { name: payment_name });
+    }
+
+    let position = state.valid_payments.as_ref().unwrap().iter().position(|x| x.name == payment_name);
+         
+    if position.is_none(){
+        return Err(ContractError::UnknownPaymentName { name: payment_name });  
+    }
+    else{ 
+        state.valid_payments.as_mut().unwrap().remove(position.unwrap());
+    }
+    
+    CONFIG_ITEM.save(deps.storage, &state)?;
+    Ok(Response::default())
+}
+
+#[entry_point]
+pub fn query(
+    deps: Deps,
+    _env: Env,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    match msg {   
+        QueryMsg::GetPackBuildInfo {} => to_binary(&query_pack_build_info(deps)?),  
+        QueryMsg::GetNumUserHistory { permit } => to_binary(&query_num_user_history(deps, get_querier(deps, &_env, permit)?)?),
+        QueryMsg::GetUserHistory {permit, start_page, page_size} => to_binary(&query_user_history(deps, get_querier(deps, &_env, permit)?, start_page, page_size)?),
+        QueryMsg::GetBuildCredits { permit } => to_binary(&query_build_credits(deps, get_querier(deps, &_env, permit)?)?),
+        QueryMsg::GetNumUserHistoryWithKey { address, key } => to_binary(&query_num_user_history(deps, get_key_querier(deps, address, key)?)?),
+        QueryMsg::GetUserHistoryWithKey { address, key, start_page, page_size } => to_binary(&query_user_history(deps, get_key_querier(deps, address, key)?, start_page, page_size)?),
+        QueryMsg::GetBuildCreditsWithKey { address, key } => to_binary(&query_build_credits(deps, get_key_querier(deps, address, key)?)?),
+        QueryMsg::GetLevels { } => to_binary(&query_levels(deps)?),
+        QueryMsg::GetXpForLevel { level } => to_binary(&query_xp_for_level(deps, level)?),
+        QueryMsg::GetRank { token_id } => to_binary(&query_rank(deps, token_id)?),
+        QueryMsg::GetNumRanks { } => to_binary(&query_num_ranks(deps)?),
+        QueryMsg::GetRanks { start_page, page_size } => to_binary(&query_ranks(deps, start_page, page_size)?),
+        QueryMsg::GetNumPacks { } => to_binary(&query_num_packs(deps)?),
+        QueryMsg::GetPacks { start_page, page_size } => to_binary(&query_packs(deps, start_page, page_size)?),
+        QueryMsg::PreviewPackBuild { main_token_id, token_ids } => to_binary(&query_preview_pack_build(deps, _env, main_token_id, token_ids)?),
+        QueryMsg::GetPackCapacity { main_token_id } => to_binary(&query_pack_capacity(deps, main_token_id )?),
+        QueryMsg::GetPackMembers { main_token_id } => to_binary(&query_pack_members(deps, main_token_id )?),
+        QueryMsg::GetPackMembersTraits { main_token_id } => to_binary(&query_pack_member_traits(deps, main_token_id )?),
+        QueryMsg::GetHolding { addr } => to_binary(&query_holding(deps, addr)?),
+        QueryMsg::GetLeaderboard { start, limit, order_by } => to_binary(&query_leaderboard(deps, start, limit, order_by)?),
+        QueryMsg::GetPackPosition { main_token_id } => to_binary(&query_pack_position(deps, main_token_id)?),
+        QueryMsg::GetPackByName { name } => to_binary(&query_pack_by_name(deps, name)?),
+        QueryMsg::GetPackOfMember { token_id } => to_binary(&query_pack_of_member(deps, token_id)?),
+    }
+}
+
+ // Get pack members, get distinct traits 
+fn query_pack_build_info(
+    deps: Deps,
+) -> StdResult<BuildInfoResponse> { 
+    let state = CONFIG_ITEM.load(deps.storage)?;
+
+    Ok(BuildInfoResponse { pack_max: state.pack_max, member_requirement: state.member_requirement, pack_rank_rule: state.pack_rank_rule, is_rename_payment_needed: state.is_rename_payment_needed, unique_pack_names: state.unique_pack_names, total_burned: state.total_burned, valid_payments: state.valid_payments })
+} 
+ 
+fn query_num_user_history(
+    deps: Deps, 
+    user_raw: CanonicalAddr
+) -> StdResult<u32> { 
+    let history_store = HISTORY_STORE.add_suffix(&user_raw);
+    let num = history_store.get_len(deps.storage)?;
+    Ok(num)
+}  
+
+fn query_user_history(
+    deps: Deps, 
+    user_raw: CanonicalAddr,
+    start_page: u32, 
+    page_size: u32
+) -> StdResult<Vec<HistoryToken>> {
+    let history_store = HISTORY_STORE.add_suffix(&user_raw); 
+    let history = history_store.paging(deps.storage, start_page, page_size)?;
+    Ok(history)
+} 
+
+fn query_build_credits(
+    deps: Deps, 
+    user_raw: CanonicalAddr
+) -> StdResult<u32> {
+    let credits = BUILD_CREDIT_STORE.get(deps.storage, &user_raw).unwrap_or_else(Vec::new);
+    //rename credits can't pay for a build
+    Ok(credits.iter().filter(|x| x.kind == CreditKind::Build).map(|x| x.quantity).sum())
+}
+
+fn query_levels(
+    deps: Deps
+) -> StdResult<Vec<Level>> {
+    let levels = LEVEL_ITEM.load(deps.storage)?;
+    Ok(levels)
+}
+
+fn query_xp_for_level(
+    deps: Deps,
+    level: u16
+) -> StdResult<u32> {
+    let levels = LEVEL_ITEM.load(deps.storage)?;
+    let level = levels.iter().find(|x| x.level == level)
+        .ok_or_else(|| StdError::generic_err(format!("Level {} doesn't exist", level)))?;
+    Ok(level.xp_needed)
+}
+
+fn query_rank(
+    deps: Deps,
+    token_id: String
+) -> StdResult<Option<u16>> {
+    let rank = RANK_STORE.get(deps.storage, &token_id);
+    Ok(rank)
+}
+
+fn query_num_ranks(
+    deps: Deps
+) -> StdResult<u32> {
+    let num_ranks = RANK_STORE.get_len(deps.storage)?;
+    Ok(num_ranks)
+}
+
+fn query_ranks(
+    deps: Deps, 
+    start_page: u32, 
+    page_size: u32
+) -> StdResult<Vec<Rank>> {
+    let ranks = RANK_STORE.paging(deps.storage, start_page, page_size)?
+        .into_iter()
+        .map(|(token_id, rank)| Rank { token_id: token_id, rank: rank })
+        .collect();
+    Ok(ranks)
+}
+
+fn query_num_packs(
+    deps: Deps
+) -> StdResult<u32> {
+    let num_staked_keys = PACK_MAIN_STORE.get_len(deps.storage).unwrap();
+    Ok(num_staked_keys)
+}
+
+fn query_packs(
+    deps: Deps, 
+    start_page: u32, 
+    page_size: u32
+) -> StdResult<Vec<PackMain>> {
+    let packs = PACK_MAIN_STORE.paging(deps.storage, start_page, page_size)?; 
+
+    let mut packs_mut: Vec<PackMain> = Vec::new();
+ 
+    for (index, (key_value, value)) in packs.iter().enumerate() {
+        packs_mut.push(value.clone());
+    } 
+  
+    Ok(packs_mut)
+}
+ 
+fn query_leaderboard(
+    deps: Deps, 
+    start: u32, 
+    limit: u32,
+    order_by: LeaderboardOrder
+) -> StdResult<Vec<LeaderboardEntry>> {
+    let (scores_item, index_store) = leaderboard_index(&order_by);
+    let limit = limit.min(MAX_LEADERBOARD_LIMIT) as usize;
+
+    let mut entries: Vec<LeaderboardEntry> = Vec::new();
+    let mut position: u32 = 0;
+    for score in scores_item.may_load(deps.storage)?.unwrap_or_default() {
+        if entries.len() >= limit {
+            break;
+        }
+        let packs = index_store.add_suffix(&score.to_be_bytes());
+        let len = packs.get_len(deps.storage)?;
+        //scores that end before the page are skipped by their length alone
+        if position + len <= start {
+            position = position + len;
+            continue;
+        }
+        let mut token_ids: Vec<String> = packs.iter_keys(deps.storage)?.collect::<StdResult<Vec<String>>>()?;
+        //ties are ordered by token id
+        token_ids.sort();
+        for token_id in token_ids.iter() {
+            position = position + 1;
+            if position <= start || entries.len() >= limit {
+                continue;
+            }
+            if let Some(pack) = PACK_MAIN_STORE.get(deps.storage, token_id) {
+                entries.push(LeaderboardEntry {
+                    position: position,
+                    pack: pack
+                });
+            }
+        }
+    }
+    Ok(entries)
+}
+
+fn leaderboard_position(
+    storage: &dyn Storage,
+    order: &LeaderboardOrder,
+    token_id: &str,
+    score: u32
+) -> StdResult<Option<u32>> {
+    let (scores_item, index_store) = leaderboard_index(order);
+    let packs = index_store.add_suffix(&score.to_be_bytes());
+    if packs.get(storage, &token_id.to_string()).is_none() {
+        return Ok(None);
+    }
+    let mut position: u32 = 1;
+    for higher in scores_item.may_load(storage)?.unwrap_or_default().iter().take_while(|x| **x > score) {
+        position = position + index_store.add_suffix(&higher.to_be_bytes()).get_len(storage)?;
+    }
+    for id in packs.iter_keys(storage)? {
+        if id?.as_str() < token_id {
+            position = position + 1;
+        }
+    }
+    Ok(Some(position))
+}
+
+fn query_pack_position(
+    deps: Deps, 
+    main_token_id: String
+) -> StdResult<PackPositionResponse> {
+    let pack = match PACK_MAIN_STORE.get(deps.storage, &main_token_id) {
+        Some(pack) => pack,
+        None => return Ok(PackPositionResponse { pack_rank_position: None, pack_count_position: None })
+    };
+    Ok(PackPositionResponse {
+        pack_rank_position: leaderboard_position(deps.storage, &LeaderboardOrder::PackRank, &main_token_id, pack.pack_rank)?,
+        pack_count_position: leaderboard_position(deps.storage, &LeaderboardOrder::PackCount, &main_token_id, pack.pack_count as u32)?
+    })
+}
+
+fn query_pack_by_name(
+    deps: Deps, 
+    name: String
+) -> StdResult<Option<PackMain>> {
+    let pack = PACK_NAME_STORE.get(deps.storage, &normalize_pack_name(&name))
+        .and_then(|token_id| PACK_MAIN_STORE.get(deps.storage, &token_id));
+    Ok(pack)
+}
+
+fn query_pack_of_member(
+    deps: Deps, 
+    token_id: String
+) -> StdResult<Option<PackMain>> {
+    let pack = MEMBER_PACK_STORE.get(deps.storage, &token_id)
+        .and_then(|main_token_id| PACK_MAIN_STORE.get(deps.storage, &main_token_id));
+    Ok(pack)
+}
+
+fn query_preview_pack_build(
+    deps: Deps, 
+    env: Env,
+    main_token_id: String,
+    token_ids: Vec<String>
+) -> StdResult<PackBuildPreviewResponse> {
+    let state = CONFIG_ITEM.load(deps.storage)?;
+    //the alpha may be sent along with its members, same as in a build
+    let member_ids: Vec<String> = token_ids.into_iter().filter(|x| x != &main_token_id).collect();
+    //every member is checked so that all of those blocking the build are reported
+    let mut errors: Vec<String> = Vec::new();
+    if let Err(err) = check_unique(&member_ids) {
+        errors.push(err.to_string());
+    }
+    let levels = LEVEL_ITEM.load(deps.storage)?;
+    match required_member_xp(&state, &levels) {
+        Ok(required_xp) => {
+            let viewer = Some(ViewerInfo {
+                address: env.contract.address.to_string(),
+                viewing_key: state.viewing_key.as_ref().unwrap().to_string(),
+            });
+            for token_id in member_ids.iter() {
+                if let Err(err) = plan_member(deps, &state, &levels, required_xp, &viewer, token_id, None) {
+                    errors.push(err.to_string());
+                }
+            }
+        },
+        Err(err) => errors.push(err.to_string())
+    }
+    //the tokens are still with their owner so only the build itself is checked
+    let plan = if errors.is_empty() {
+        plan_pack_build(deps, &env, &state, &main_token_id, &member_ids, None, None)
+            .map_err(|err| vec![err.to_string()])
+    } else {
+        Err(errors)
+    };
+    //the preview has no name or builder, so a build can still fail on these
+    let mut unchecked = vec!["pack_name".to_string()];
+    if state.is_payment_needed {
+        unchecked.push("build_credit".to_string());
+    }
+    let preview = match plan {
+        Ok(plan) => PackBuildPreviewResponse {
+            xp: Some(plan.xp),
+            level: Some(plan.level),
+            pack_size: Some(plan.pack_size),
+            pack_rank: Some(plan.pack_rank),
+            pack_rank_change: Some(plan.pack_rank_change),
+            errors: vec![],
+            unchecked: unchecked
+        },
+        Err(errors) => PackBuildPreviewResponse {
+            xp: None,
+            level: None,
+            pack_size: None,
+            pack_rank: None,
+            pack_rank_change: None,
+            errors: errors,
+            unchecked: unchecked
+        }
+    };
+    Ok(preview)
+}
+
+fn query_pack_capacity(
+    deps: Deps, 
+    main_token_id: String
+) -> StdResult<PackCapacityResponse> {
+    let state = CONFIG_ITEM.load(deps.storage)?;
+    let pack_count = PACK_MEMBER_STORE.get(deps.storage, &main_token_id).map_or(0, |x| x.len() as u16);
+    Ok(PackCapacityResponse { pack_max: state.pack_max, pack_count: pack_count, slots_left: state.pack_max.saturating_sub(pack_count) })
+}
+
+fn query_pack_members(
+    deps: Deps, 
+    main_token_id: String
+) -> StdResult<Vec<PackMember>> {
+    let pack_members = PACK_MEMBER_STORE.get(deps.storage, &main_token_id).unwrap_or_else(Vec::new);
+    Ok(pack_members)
+}
+
+fn query_pack_member_traits(
+    deps: Deps, 
+    main_token_id: String
+) -> StdResult<Vec<Trait>> {
+    
+    let pack_members = PACK_MEMBER_STORE.get(deps.storage, &main_token_id).unwrap_or_else(Vec::new);
+    let mut distinct_traits: Vec<Trait> = Vec::new();
+    for (index, value) in pack_members.iter().enumerate() {
+        for member_trait in &value.attributes {
+            if !distinct_traits.contains(&member_trait) {
+                distinct_traits.push(member_trait.clone());
+            }
+        }
+    }
+    Ok(distinct_traits)
+}
+
+fn query_holding(
+    deps: Deps, 
+    addr: Addr
+) -> StdResult<Vec<String>> { 
+    let holding: Vec<String> = INHOLDING_NFT_STORE.get(deps.storage,&deps.api.addr_canonicalize(&addr.to_string())?).unwrap_or(Vec::new());
+    Ok(holding)
+}
+
+fn get_querier(
+    deps: Deps,
+    env: &Env,
+    permit: Permit,
+) -> StdResult<CanonicalAddr> {
+    //permits are signed for this contract, so check them against the address in env
+    let querier = deps.api.addr_canonicalize(&validate(
+        deps,
+        PREFIX_REVOKED_PERMITS,
+        &permit,
+        env.contract.address.to_string(),
+        None
+    )?)?;
+    if !permit.check_permission(&secret_toolkit::permit::TokenPermissions::Owner) {
+        return Err(StdError::generic_err(format!(
+            "Owner permission is required for history and build credit queries, got permissions {:?}",
+            permit.params.permissions
+        )));
+    }
+    Ok(querier)
+}
+
+fn get_key_querier(
+    deps: Deps,
+    address: Addr,
+    key: String
+) -> StdResult<CanonicalAddr> {
+    let address = deps.api.addr_validate(address.as_str())?;
+    ViewingKey::check(deps.storage, address.as_str(), &key)?;
+    deps.api.addr_canonicalize(address.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{Api, ContractResult, OwnedDeps, SystemResult, WasmMsg, WasmQuery};
+    use crate::msg::{RankTier, TraitBonus};
+    use crate::state::{LegacyHistoryToken, LegacyPaymentContractInfo, LegacyState};
+    use secret_toolkit::permit::{PermitParams, PermitSignature, PubKey, SignedPermit, TokenPermissions};
+    use serde::{Deserialize, Serialize};
+
+    const OWNER: &str = "owner";
+    const ALICE: &str = "alice";
+    const NFT_CONTRACT: &str = "nft_contract";
+
+    type MockDeps = OwnedDeps<MockStorage, MockApi, MockQuerier>;
+
+    #[derive(Serialize, Clone)]
+    struct MockDossier {
+        owner: Option<String>,
+        public_metadata: Option<Metadata>,
+        private_metadata: Option<Metadata>,
+        display_private_metadata_error: Option<String>,
+        owner_is_public: bool,
+        private_metadata_is_public: bool,
+        transferable: bool,
+        unwrapped: bool,
+    }
+
+    #[derive(Serialize)]
+    struct MockDossierResponse {
+        nft_dossier: MockDossier,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename_all = "snake_case")]
+    enum MockNftQuery {
+        NftDossier { token_id: String },
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct MockTransfer {
+        recipient: String,
+        token_ids: Vec<String>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[serde(rename_all = "snake_case")]
+    enum MockNftHandle {
+        SetMetadata {
+            token_id: String,
+            public_metadata: Option<Metadata>,
+            private_metadata: Option<Metadata>,
+        },
+        BatchTransferNft {
+            transfers: Vec<MockTransfer>,
+        },
+        BatchBurnNft {
+            burns: Vec<MockBurn>,
+        },
+        TransferNft {
+            recipient: String,
+            token_id: String,
+        },
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct MockBurn {
+        token_ids: Vec<String>,
+    }
+
+    #[derive(Deserialize, Debug)]
+    #[serde(rename_all = "snake_case")]
+    enum MockSnip20Handle {
+        Transfer {
+            recipient: String,
+            amount: Uint128,
+        },
+    }
+
+    fn metadata(traits: &[(&str, &str)], media: &[&str]) -> Option<Metadata> {
+        Some(Metadata {
+            token_uri: None,
+            extension: Some(Extension {
+                attributes: Some(traits.iter().map(|(trait_type, value)| Trait {
+                    display_type: None,
+                    trait_type: Some(trait_type.to_string()),
+                    value: value.to_string(),
+                    max_value: None,
+                }).collect()),
+                media: Some(media.iter().map(|url| MediaFile {
+                    file_type: None,
+                    extension: None,
+                    authentication: None,
+                    url: url.to_string(),
+                }).collect()),
+                ..Extension::default()
+            }),
+        })
+    }
+
+    fn dossier(public_metadata: Option<Metadata>, private_metadata: Option<Metadata>) -> MockDossier {
+        MockDossier {
+            owner: Some(mock_env().contract.address.to_string()),
+            public_metadata,
+            private_metadata,
+            display_private_metadata_error: None,
+            owner_is_public: false,
+            private_metadata_is_public: false,
+            transferable: true,
+            unwrapped: true,
+        }
+    }
+
+    fn mock_deps(dossiers: Vec<(&str, MockDossier)>) -> MockDeps {
+        let mut deps = mock_dependencies();
+        let dossiers: HashMap<String, MockDossier> = dossiers.into_iter().map(|(id, d)| (id.to_string(), d)).collect();
+        deps.querier.update_wasm(move |query| match query {
+            WasmQuery::Smart { msg, .. } => {
+                let nft_query: MockNftQuery = from_binary(msg).unwrap();
+                let MockNftQuery::NftDossier { token_id } = nft_query;
+                match dossiers.get(&token_id) {
+                    Some(d) => SystemResult::Ok(ContractResult::Ok(
+                        to_binary(&MockDossierResponse { nft_dossier: d.clone() }).unwrap(),
+                    )),
+                    None => SystemResult::Ok(ContractResult::Err(format!("token {} not found", token_id))),
+                }
+            }
+            _ => panic!("unexpected wasm query"),
+        });
+        deps
+    }
+
+    fn init(deps: &mut MockDeps) {
+        let msg = InstantiateMsg {
+            entropy: "entropy".to_string(),
+            nft_contract: ContractInfo {
+                code_hash: "nft_hash".to_string(),
+                address: Addr::unchecked(NFT_CONTRACT),
+            },
+            is_payment_needed: false,
+            valid_payments: None,
+            receiving_address: Addr::unchecked("treasury"),
+            pack_max: 10,
+            collection_size: 100,
+            level_cap: 5,
+            member_requirement: None,
+            trait_names: None,
+            pack_rank_rule: None,
+            levels: vec![
+                Level { level: 1, xp_needed: 0 },
+                Level { level: 2, xp_needed: 100 },
+                Level { level: 3, xp_needed: 464 },
+                Level { level: 4, xp_needed: 1000 },
+                Level { level: 5, xp_needed: 2000 },
+            ],
+            ranks: vec![
+                Rank { token_id: "10".to_string(), rank: 10 },
+                Rank { token_id: "11".to_string(), rank: 20 },
+                Rank { token_id: "12".to_string(), rank: 30 },
+            ],
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), msg).unwrap();
+    }
+
+    fn member(token_id: &str, rank: u16) -> PackMember {
+        PackMember { token_id: token_id.to_string(), rank, attributes: vec![] }
+    }
+
+    fn seed_pack(deps: &mut MockDeps, main_token_id: &str, members: Vec<PackMember>) {
+        let state = CONFIG_ITEM.load(&deps.storage).unwrap();
+        save_pack_main(&mut deps.storage, &state, &PackMain {
+            token_id: main_token_id.to_string(),
+            pack_rank: calculate_pack_rank(&state, &members),
+            pack_count: members.len() as u16,
+            name: "Moon Howlers".to_string(),
+        }).unwrap();
+        save_pack_members(&mut deps.storage, main_token_id, &members).unwrap();
+    }
+
+    fn batch_receive(deps: &mut MockDeps, sender: &str, token_ids: &[&str], msg: &HandleReceiveMsg) -> Result<Response, ContractError> {
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(sender, &[]),
+            ExecuteMsg::BatchReceiveNft {
+                from: Addr::unchecked(ALICE),
+                token_ids: token_ids.iter().map(|x| x.to_string()).collect(),
+                msg: Some(to_binary(msg).unwrap()),
+            },
+        )
+    }
+
+    fn nft_msgs(res: &Response) -> Vec<MockNftHandle> {
+        res.messages.iter().map(|sub| match &sub.msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => from_binary(msg).unwrap(),
+            _ => panic!("unexpected message"),
+        }).collect()
+    }
+
+    fn trait_value(meta: &Option<Metadata>, trait_type: &str) -> Option<String> {
+        meta.as_ref()?.extension.as_ref()?.attributes.as_ref()?.iter()
+            .find(|x| x.trait_type.as_deref() == Some(trait_type))
+            .map(|x| x.value.clone())
+    }
+
+    fn media_urls(meta: &Option<Metadata>) -> Vec<String> {
+        meta.as_ref().unwrap().extension.as_ref().unwrap().media.as_ref().unwrap().iter().map(|x| x.url.clone()).collect()
+    }
+
+    fn transfer_msg_for(token_id: &str) -> HandleReceiveMsg {
+        HandleReceiveMsg::ReceiveTransferBuild {
+            transfer_build: PackTransferMsg {
+                main_token_id: "1".to_string(),
+                transfer_to_token_id: "2".to_string(),
+                token_id: token_id.to_string(),
+            },
+        }
+    }
+
+    fn transfer_deps() -> MockDeps {
+        let mut deps = mock_deps(vec![
+            ("1", dossier(
+                metadata(&[("Alpha", "Yes"), ("XP", "500"), ("LVL", "3"), ("Pack", "2"), ("Pack Rank", "170")], &["alpha1", "pub10", "pub11"]),
+                metadata(&[], &["priv10", "priv11"]),
+            )),
+            ("2", dossier(
+                metadata(&[("Alpha", "Yes"), ("XP", "500"), ("LVL", "3"), ("Pack", "0")], &["alpha2"]),
+                metadata(&[], &[]),
+            )),
+            ("3", dossier(
+                metadata(&[("XP", "500"), ("LVL", "3")], &["wolf3"]),
+                metadata(&[], &[]),
+            )),
+        ]);
+        init(&mut deps);
+        seed_pack(&mut deps, "1", vec![member("10", 10), member("11", 20)]);
+        deps
+    }
+
+    #[test]
+    fn transfer_pack_moves_member_between_alphas() {
+        let mut deps = transfer_deps();
+
+        let res = batch_receive(&mut deps, NFT_CONTRACT, &["1", "2"], &transfer_msg_for("10")).unwrap();
+
+        let main_members = PACK_MEMBER_STORE.get(&deps.storage, &"1".to_string()).unwrap();
+        let transfer_to_members = PACK_MEMBER_STORE.get(&deps.storage, &"2".to_string()).unwrap();
+        assert_eq!(main_members, vec![member("11", 20)]);
+        assert_eq!(transfer_to_members, vec![member("10", 10)]);
+
+        let main_pack = PACK_MAIN_STORE.get(&deps.storage, &"1".to_string()).unwrap();
+        let transfer_to_pack = PACK_MAIN_STORE.get(&deps.storage, &"2".to_string()).unwrap();
+        assert_eq!((main_pack.pack_count, main_pack.pack_rank), (1, 80));
+        assert_eq!((transfer_to_pack.pack_count, transfer_to_pack.pack_rank), (1, 90));
+        assert_eq!(main_pack.name, "Moon Howlers");
+
+        let msgs = nft_msgs(&res);
+        assert_eq!(msgs.len(), 3);
+        match &msgs[0] {
+            MockNftHandle::SetMetadata { token_id, public_metadata, private_metadata } => {
+                assert_eq!(token_id, "1");
+                assert_eq!(trait_value(public_metadata, "Pack"), Some("1".to_string()));
+                assert_eq!(trait_value(public_metadata, "Pack Rank"), Some("80".to_string()));
+                assert_eq!(media_urls(public_metadata), vec!["alpha1", "pub11"]);
+                assert_eq!(media_urls(private_metadata), vec!["priv11"]);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        match &msgs[1] {
+            MockNftHandle::SetMetadata { token_id, public_metadata, private_metadata } => {
+                assert_eq!(token_id, "2");
+                assert_eq!(trait_value(public_metadata, "Pack"), Some("1".to_string()));
+                assert_eq!(trait_value(public_metadata, "Pack Rank"), Some("90".to_string()));
+                assert_eq!(media_urls(public_metadata), vec!["alpha2", "pub10"]);
+                assert_eq!(media_urls(private_metadata), vec!["priv10"]);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        match &msgs[2] {
+            MockNftHandle::BatchTransferNft { transfers } => {
+                assert_eq!(transfers.len(), 1);
+                assert_eq!(transfers[0].recipient, ALICE);
+                assert_eq!(transfers[0].token_ids, vec!["1", "2"]);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+
+        assert!(INHOLDING_NFT_STORE.get(&deps.storage, &deps.api.addr_canonicalize(ALICE).unwrap()).is_none());
+    }
+
+    #[test]
+    fn transfer_pack_rejects_invalid_requests() {
+        let mut deps = transfer_deps();
+
+        // only the nft contract can deliver the alphas
+        assert!(batch_receive(&mut deps, ALICE, &["1", "2"], &transfer_msg_for("10")).is_err());
+        // both alphas have to be sent
+        assert!(batch_receive(&mut deps, NFT_CONTRACT, &["1"], &transfer_msg_for("10")).is_err());
+        // the token has to be a member of the main alpha's pack
+        assert!(batch_receive(&mut deps, NFT_CONTRACT, &["1", "2"], &transfer_msg_for("12")).is_err());
+        // the destination has to be an alpha
+        let msg = HandleReceiveMsg::ReceiveTransferBuild {
+            transfer_build: PackTransferMsg {
+                main_token_id: "1".to_string(),
+                transfer_to_token_id: "3".to_string(),
+                token_id: "10".to_string(),
+            },
+        };
+        assert!(batch_receive(&mut deps, NFT_CONTRACT, &["1", "3"], &msg).is_err());
+
+        // nothing was written by the failed attempts
+        assert_eq!(PACK_MEMBER_STORE.get(&deps.storage, &"1".to_string()).unwrap().len(), 2);
+        assert!(PACK_MEMBER_STORE.get(&deps.storage, &"2".to_string()).is_none());
+    }
+
+    #[test]
+    fn transfer_pack_removes_emptied_pack() {
+        let mut deps = mock_deps(vec![
+            ("1", dossier(
+                metadata(&[("Alpha", "Yes"), ("XP", "500"), ("LVL", "3"), ("Pack", "1"), ("Pack Rank", "90")], &["alpha1", "pub10"]),
+                metadata(&[], &["priv10"]),
+            )),
+            ("2", dossier(
+                metadata(&[("Alpha", "Yes"), ("XP", "500"), ("LVL", "3"), ("Pack", "0")], &["alpha2"]),
+                metadata(&[], &[]),
+            )),
+        ]);
+        init(&mut deps);
+        seed_pack(&mut deps, "1", vec![member("10", 10)]);
+
+        batch_receive(&mut deps, NFT_CONTRACT, &["1", "2"], &transfer_msg_for("10")).unwrap();
+
+        // the emptied pack is gone along with its leaderboard entry and name
+        assert!(PACK_MEMBER_STORE.get(&deps.storage, &"1".to_string()).is_none());
+        assert!(PACK_MAIN_STORE.get(&deps.storage, &"1".to_string()).is_none());
+        let ranked = PACK_RANK_INDEX_STORE.add_suffix(&90u32.to_be_bytes());
+        assert!(ranked.get(&deps.storage, &"1".to_string()).is_none());
+        assert_eq!(ranked.get_len(&deps.storage).unwrap(), 1);
+        assert!(PACK_NAME_STORE.get(&deps.storage, &"moon howlers".to_string()).is_none());
+        assert_eq!(MEMBER_PACK_STORE.get(&deps.storage, &"10".to_string()), Some("2".to_string()));
+        assert_eq!(PACK_MAIN_STORE.get(&deps.storage, &"2".to_string()).unwrap().pack_count, 1);
+    }
+
+    fn alpha_dossier() -> MockDossier {
+        dossier(
+            metadata(&[("Alpha", "Yes"), ("XP", "500"), ("LVL", "3"), ("Pack", "0")], &["alpha1"]),
+            metadata(&[], &[]),
+        )
+    }
+
+    fn wolf_dossier(xp: &str, media: &str) -> MockDossier {
+        dossier(
+            metadata(&[("Fur", "Grey"), ("XP", xp), ("LVL", "3")], &[format!("pub{}", media).as_str()]),
+            metadata(&[], &[format!("priv{}", media).as_str()]),
+        )
+    }
+
+    fn build_msg() -> HandleReceiveMsg {
+        HandleReceiveMsg::ReceivePackBuild {
+            pack_build: PackBuildMsg { main_token_id: "1".to_string(), name: "Moon Howlers".to_string() },
+        }
+    }
+
+    fn join_with(alpha: MockDossier, wolf: MockDossier) -> Result<Response, ContractError> {
+        let mut deps = mock_deps(vec![("1", alpha), ("10", wolf)]);
+        init(&mut deps);
+        batch_receive(&mut deps, NFT_CONTRACT, &["1", "10"], &build_msg())
+    }
+
+    #[test]
+    fn join_pack_applies_member_requirement() {
+        let join = |requirement: MemberRequirement, xp: &str| {
+            let mut deps = mock_deps(vec![("1", alpha_dossier()), ("10", wolf_dossier(xp, "10"))]);
+            init(&mut deps);
+            let mut state = CONFIG_ITEM.load(&deps.storage).unwrap();
+            state.member_requirement = requirement;
+            CONFIG_ITEM.save(&mut deps.storage, &state).unwrap();
+            batch_receive(&mut deps, NFT_CONTRACT, &["1", "10"], &build_msg())
+        };
+        let too_low = |level: u16, required: u16| ContractError::LevelTooLow { token_id: "10".to_string(), level, required };
+
+        let min_xp = MemberRequirement::MinXp { xp: 1000 };
+        assert_eq!(join(min_xp.clone(), "999").unwrap_err(), too_low(3, 4));
+        assert!(join(min_xp, "1000").is_ok());
+
+        let min_level = MemberRequirement::MinLevel { level: 2 };
+        assert_eq!(join(min_level.clone(), "99").unwrap_err(), too_low(1, 2));
+        assert!(join(min_level, "100").is_ok());
+
+        // a level that has since left the level table can't be met
+        assert_eq!(join(MemberRequirement::MinLevel { level: 9 }, "5000").unwrap_err(), ContractError::UnknownLevel { level: 9 });
+    }
+
+    #[test]
+    fn join_pack_builds_pack() {
+        let mut deps = mock_deps(vec![
+            ("1", alpha_dossier()),
+            ("10", wolf_dossier("500", "10")),
+            ("11", wolf_dossier("600", "11")),
+        ]);
+        init(&mut deps);
+
+        let res = batch_receive(&mut deps, NFT_CONTRACT, &["10", "1", "11"], &build_msg()).unwrap();
+
+        let pack = PACK_MAIN_STORE.get(&deps.storage, &"1".to_string()).unwrap();
+        assert_eq!(pack, PackMain { token_id: "1".to_string(), pack_rank: 170, pack_count: 2, name: "Moon Howlers".to_string() });
+        let members = PACK_MEMBER_STORE.get(&deps.storage, &"1".to_string()).unwrap();
+        assert_eq!(members.iter().map(|x| x.token_id.as_str()).collect::<Vec<&str>>(), vec!["10", "11"]);
+        assert_eq!(CONFIG_ITEM.load(&deps.storage).unwrap().total_burned, 2);
+        assert_eq!(res.attributes.len(), 1);
+
+        let msgs = nft_msgs(&res);
+        assert_eq!(msgs.len(), 3);
+        match &msgs[0] {
+            MockNftHandle::BatchBurnNft { burns } => assert_eq!(burns[0].token_ids, vec!["10", "11"]),
+            other => panic!("unexpected message {:?}", other),
+        }
+        match &msgs[1] {
+            MockNftHandle::SetMetadata { token_id, public_metadata, private_metadata } => {
+                assert_eq!(token_id, "1");
+                assert_eq!(public_metadata.as_ref().unwrap().extension.as_ref().unwrap().name, Some("Moon Howlers".to_string()));
+                assert_eq!(trait_value(public_metadata, "XP"), Some("1600".to_string()));
+                assert_eq!(trait_value(public_metadata, "LVL"), Some("4".to_string()));
+                assert_eq!(trait_value(public_metadata, "Pack"), Some("2".to_string()));
+                assert_eq!(trait_value(public_metadata, "Pack Rank"), Some("170".to_string()));
+                assert_eq!(media_urls(public_metadata), vec!["alpha1", "pub10", "pub11"]);
+                assert_eq!(media_urls(private_metadata), vec!["priv10", "priv11"]);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        match &msgs[2] {
+            MockNftHandle::TransferNft { recipient, token_id } => {
+                assert_eq!(recipient, ALICE);
+                assert_eq!(token_id, "1");
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    #[test]
+    fn preview_pack_build_matches_join_without_saving() {
+        let mut deps = mock_deps(vec![
+            ("1", alpha_dossier()),
+            ("10", wolf_dossier("500", "10")),
+            ("11", wolf_dossier("600", "11")),
+            ("12", wolf_dossier("100", "12")),
+        ]);
+        init(&mut deps);
+
+        let preview = |deps: &MockDeps, ids: &[&str]| -> PackBuildPreviewResponse {
+            let msg = QueryMsg::PreviewPackBuild {
+                main_token_id: "1".to_string(),
+                token_ids: ids.iter().map(|x| x.to_string()).collect(),
+            };
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap()
+        };
+
+        let res = preview(&deps, &["10", "1", "11"]);
+        assert_eq!(res, PackBuildPreviewResponse {
+            xp: Some(1600),
+            level: Some(4),
+            pack_size: Some(2),
+            pack_rank: Some(170),
+            pack_rank_change: Some(170),
+            errors: vec![],
+            unchecked: vec!["pack_name".to_string()],
+        });
+        assert!(PACK_MAIN_STORE.get(&deps.storage, &"1".to_string()).is_none());
+        assert!(PACK_MEMBER_STORE.get(&deps.storage, &"1".to_string()).is_none());
+
+        // every member blocking the build is reported, not just the first
+        let res = preview(&deps, &["12", "10", "13", "12"]);
+        assert_eq!(res.xp, None);
+        assert_eq!(res.errors, vec![
+            ContractError::DuplicateToken { token_id: "12".to_string() }.to_string(),
+            ContractError::LevelTooLow { token_id: "12".to_string(), level: 2, required: 3 }.to_string(),
+            ContractError::RankNotFound { token_id: "13".to_string() }.to_string(),
+            ContractError::LevelTooLow { token_id: "12".to_string(), level: 2, required: 3 }.to_string(),
+        ]);
+    }
+
+    #[test]
+    fn preview_pack_build_works_before_tokens_are_sent() {
+        // the owner still holds the tokens and the contract can't see their private metadata
+        let owned = |d: MockDossier| MockDossier { owner: Some(ALICE.to_string()), private_metadata: None, ..d };
+        let mut deps = mock_deps(vec![
+            ("1", owned(alpha_dossier())),
+            ("10", owned(wolf_dossier("500", "10"))),
+        ]);
+        init(&mut deps);
+        enable_payments(&mut deps);
+
+        let res: PackBuildPreviewResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::PreviewPackBuild {
+            main_token_id: "1".to_string(),
+            token_ids: vec!["10".to_string()],
+        }).unwrap()).unwrap();
+        assert_eq!(res.errors, Vec::<String>::new());
+        assert_eq!((res.xp, res.level, res.pack_size, res.pack_rank), (Some(1000), Some(4), Some(1), Some(90)));
+        assert_eq!(res.unchecked, vec!["pack_name".to_string(), "build_credit".to_string()]);
+    }
+
+    #[test]
+    fn join_pack_reports_broken_member_metadata() {
+        let missing = |field: &str| ContractError::MissingMetadata { token_id: "10".to_string(), field: field.to_string() };
+
+        let mut no_public = wolf_dossier("500", "10");
+        no_public.public_metadata = None;
+        assert_eq!(join_with(alpha_dossier(), no_public).unwrap_err(), missing("public_metadata"));
+
+        let mut no_extension = wolf_dossier("500", "10");
+        no_extension.public_metadata = Some(Metadata { token_uri: Some("ipfs://wolf".to_string()), extension: None });
+        assert_eq!(join_with(alpha_dossier(), no_extension).unwrap_err(), missing("public_metadata.extension"));
+
+        let mut no_private = wolf_dossier("500", "10");
+        no_private.private_metadata = None;
+        assert_eq!(join_with(alpha_dossier(), no_private).unwrap_err(), missing("private_metadata"));
+
+        let mut no_attributes = wolf_dossier("500", "10");
+        no_attributes.public_metadata.as_mut().unwrap().extension.as_mut().unwrap().attributes = None;
+        assert_eq!(join_with(alpha_dossier(), no_attributes).unwrap_err(), missing("public_metadata.extension.attributes"));
+
+        let no_xp = dossier(metadata(&[("Fur", "Grey")], &["pub10"]), metadata(&[], &["priv10"]));
+        assert_eq!(join_with(alpha_dossier(), no_xp).unwrap_err(), ContractError::MissingTrait {
+            token_id: "10".to_string(),
+            trait_type: "XP".to_string(),
+        });
+
+        assert_eq!(join_with(alpha_dossier(), wolf_dossier("lots", "10")).unwrap_err(), ContractError::InvalidTrait {
+            token_id: "10".to_string(),
+            trait_type: "XP".to_string(),
+            value: "lots".to_string(),
+        });
+
+        let no_media = dossier(metadata(&[("XP", "500")], &[]), metadata(&[], &["priv10"]));
+        assert_eq!(join_with(alpha_dossier(), no_media).unwrap_err(), ContractError::MissingMedia {
+            token_id: "10".to_string(),
+            metadata_type: "public".to_string(),
+        });
+
+        // xp that would overflow the pack total is reported instead of aborting
+        let mut deps = mock_deps(vec![
+            ("1", alpha_dossier()),
+            ("10", wolf_dossier("4294967295", "10")),
+            ("11", wolf_dossier("4294967295", "11")),
+        ]);
+        init(&mut deps);
+        assert_eq!(batch_receive(&mut deps, NFT_CONTRACT, &["1", "10", "11"], &build_msg()).unwrap_err(), ContractError::InvalidTrait {
+            token_id: "11".to_string(),
+            trait_type: "XP".to_string(),
+            value: "4294967295".to_string(),
+        });
+    }
+
+    #[test]
+    fn join_pack_reports_broken_alpha_metadata() {
+        let bad_level = dossier(
+            metadata(&[("Alpha", "Yes"), ("XP", "500"), ("LVL", "three")], &["alpha1"]),
+            metadata(&[], &[]),
+        );
+        assert_eq!(join_with(bad_level, wolf_dossier("500", "10")).unwrap_err(), ContractError::InvalidTrait {
+            token_id: "1".to_string(),
+            trait_type: "LVL".to_string(),
+            value: "three".to_string(),
+        });
+
+        let mut no_private = alpha_dossier();
+        no_private.private_metadata = None;
+        assert_eq!(join_with(no_private, wolf_dossier("500", "10")).unwrap_err(), ContractError::MissingMetadata {
+            token_id: "1".to_string(),
+            field: "private_metadata".to_string(),
+        });
+
+        let full_pack = dossier(
+            metadata(&[("Alpha", "Yes"), ("XP", "500"), ("LVL", "3"), ("Pack", "65535")], &["alpha1"]),
+            metadata(&[], &[]),
+        );
+        assert_eq!(join_with(full_pack, wolf_dossier("500", "10")).unwrap_err(), ContractError::InvalidTrait {
+            token_id: "1".to_string(),
+            trait_type: "Pack".to_string(),
+            value: "65535".to_string(),
+        });
+    }
+
+    #[test]
+    fn claim_back_returns_held_tokens() {
+        let mut deps = mock_deps(vec![]);
+        init(&mut deps);
+        let alice_raw = deps.api.addr_canonicalize(ALICE).unwrap();
+        INHOLDING_NFT_STORE.insert(&mut deps.storage, &alice_raw, &vec!["1".to_string(), "2".to_string(), "3".to_string()]).unwrap();
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), ExecuteMsg::ClaimBack {
+            token_ids: Some(vec!["2".to_string()]),
+        }).unwrap();
+        match &nft_msgs(&res)[0] {
+            MockNftHandle::BatchTransferNft { transfers } => {
+                assert_eq!(transfers[0].recipient, ALICE);
+                assert_eq!(transfers[0].token_ids, vec!["2"]);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        assert_eq!(INHOLDING_NFT_STORE.get(&deps.storage, &alice_raw).unwrap(), vec!["1", "3"]);
+
+        // tokens that aren't held can't be claimed
+        assert!(execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), ExecuteMsg::ClaimBack {
+            token_ids: Some(vec!["2".to_string()]),
+        }).is_err());
+
+        // only the admin can claim on a user's behalf
+        let admin_claim = ExecuteMsg::AdminClaimBack { owner: Addr::unchecked(ALICE), token_ids: None };
+        assert!(execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), admin_claim.clone()).is_err());
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), admin_claim).unwrap();
+        match &nft_msgs(&res)[0] {
+            MockNftHandle::BatchTransferNft { transfers } => {
+                assert_eq!(transfers[0].recipient, ALICE);
+                assert_eq!(transfers[0].token_ids, vec!["1", "3"]);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        assert!(INHOLDING_NFT_STORE.get(&deps.storage, &alice_raw).is_none());
+    }
+
+    #[test]
+    fn join_pack_respects_pack_max() {
+        let mut deps = mock_deps(vec![]);
+        init(&mut deps);
+        let members: Vec<PackMember> = (0..9).map(|x| member(&format!("m{}", x), 50)).collect();
+        seed_pack(&mut deps, "1", members);
+
+        let capacity: PackCapacityResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetPackCapacity {
+            main_token_id: "1".to_string(),
+        }).unwrap()).unwrap();
+        assert_eq!(capacity, PackCapacityResponse { pack_max: 10, pack_count: 9, slots_left: 1 });
+
+        let msg = HandleReceiveMsg::ReceivePackBuild {
+            pack_build: PackBuildMsg { main_token_id: "1".to_string(), name: "Moon Howlers".to_string() },
+        };
+        let err = batch_receive(&mut deps, NFT_CONTRACT, &["1", "10", "11"], &msg).unwrap_err();
+        assert_eq!(err, ContractError::PackFull { main_token_id: "1".to_string(), slots_left: 1 });
+    }
+
+    fn enable_payments(deps: &mut MockDeps) {
+        let mut state = CONFIG_ITEM.load(&deps.storage).unwrap();
+        state.is_payment_needed = true;
+        state.valid_payments = Some(vec![PaymentContractInfo {
+            code_hash: "snip_hash".to_string(),
+            address: Addr::unchecked("snip20"),
+            payment_needed: Uint128::from(10u128),
+            rename_price: Uint128::from(4u128),
+            name: "sSCRT".to_string(),
+        }]);
+        CONFIG_ITEM.save(&mut deps.storage, &state).unwrap();
+    }
+
+    fn pay(deps: &mut MockDeps, quantity: u16, amount: u128) -> Result<Response, ContractError> {
+        execute(deps.as_mut(), mock_env(), mock_info("snip20", &[]), ExecuteMsg::Receive {
+            sender: Addr::unchecked(ALICE),
+            from: Addr::unchecked(ALICE),
+            amount: Uint128::from(amount),
+            msg: Some(to_binary(&ReceiveMsg { quantity, kind: CreditKind::Build }).unwrap()),
+        })
+    }
+
+    #[test]
+    fn receive_adds_build_credits_for_quantity() {
+        let mut deps = mock_deps(vec![]);
+        init(&mut deps);
+        enable_payments(&mut deps);
+        let alice_raw = deps.api.addr_canonicalize(ALICE).unwrap();
+
+        assert!(pay(&mut deps, 5, 40).is_err());
+        assert!(pay(&mut deps, 0, 0).is_err());
+        let res = pay(&mut deps, 5, 50).unwrap();
+        assert!(res.messages.is_empty());
+        pay(&mut deps, 2, 20).unwrap();
+        let credits = BUILD_CREDIT_STORE.get(&deps.storage, &alice_raw).unwrap();
+        assert_eq!(credits.len(), 1);
+        assert_eq!(credits[0].quantity, 7);
+        assert_eq!(credits[0].price, Uint128::from(10u128));
+    }
+
+    #[test]
+    fn receive_rejects_bad_payments_without_panicking() {
+        let mut deps = mock_deps(vec![]);
+        init(&mut deps);
+
+        // no payment options configured
+        assert_eq!(pay(&mut deps, 1, 10).unwrap_err(), ContractError::UnknownPaymentContract { address: "snip20".to_string() });
+
+        enable_payments(&mut deps);
+        let malformed = execute(deps.as_mut(), mock_env(), mock_info("snip20", &[]), ExecuteMsg::Receive {
+            sender: Addr::unchecked(ALICE),
+            from: Addr::unchecked(ALICE),
+            amount: Uint128::from(10u128),
+            msg: Some(Binary::from(b"not json".to_vec())),
+        });
+        assert!(matches!(malformed, Err(ContractError::Std(_))));
+        // a zero quantity is reported as such whatever the amount
+        assert_eq!(pay(&mut deps, 0, 10).unwrap_err(), ContractError::InvalidQuantity {});
+    }
+
+    #[test]
+    fn add_and_update_payment_are_saved() {
+        let mut deps = mock_deps(vec![]);
+        init(&mut deps);
+        let payment = PaymentContractInfo {
+            code_hash: "snip_hash".to_string(),
+            address: Addr::unchecked("snip20"),
+            payment_needed: Uint128::from(10u128),
+            rename_price: Uint128::from(4u128),
+            name: "sSCRT".to_string(),
+        };
+
+        assert!(execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), ExecuteMsg::AddPayment { payment: payment.clone() }).is_err());
+        let res = execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), ExecuteMsg::AddPayment { payment: payment.clone() }).unwrap();
+        match &res.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, .. }) => assert_eq!(contract_addr, "snip20"),
+            _ => panic!("unexpected message"),
+        }
+        assert!(execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), ExecuteMsg::AddPayment { payment: payment.clone() }).is_err());
+
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), ExecuteMsg::UpdatePayment {
+            payment_name: "sSCRT".to_string(),
+            payment_needed: Some(Uint128::from(25u128)),
+            rename_price: None,
+            code_hash: None,
+        }).unwrap();
+        assert!(execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), ExecuteMsg::UpdatePayment {
+            payment_name: "SHD".to_string(),
+            payment_needed: Some(Uint128::from(25u128)),
+            rename_price: None,
+            code_hash: None,
+        }).is_err());
+
+        let state = CONFIG_ITEM.load(&deps.storage).unwrap();
+        assert_eq!(state.valid_payments, Some(vec![PaymentContractInfo {
+            payment_needed: Uint128::from(25u128),
+            ..payment
+        }]));
+    }
+
+    #[test]
+    fn update_config_validates_and_saves() {
+        let mut deps = mock_deps(vec![]);
+        init(&mut deps);
+        let update = |collection_size: u16| ExecuteMsg::UpdateConfig {
+            receiving_address: Some(Addr::unchecked("new_treasury")),
+            pack_max: Some(4),
+            collection_size: Some(collection_size),
+            level_cap: None,
+            is_payment_needed: Some(true),
+            nft_contract: None,
+            member_requirement: Some(MemberRequirement::MinLevel { level: 4 }),
+            trait_names: None,
+            pack_rank_rule: None,
+            is_rename_payment_needed: None,
+            unique_pack_names: None,
+        };
+
+        assert!(execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), update(200)).is_err());
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), update(30)).unwrap();
+        assert_eq!(res.attributes.len(), 5);
+        let state = CONFIG_ITEM.load(&deps.storage).unwrap();
+        assert_eq!(state.receiving_address, Addr::unchecked("new_treasury"));
+        assert_eq!(state.pack_max, 4);
+        assert_eq!(state.collection_size, 30);
+        assert_eq!(state.level_cap, 5);
+        assert!(state.is_payment_needed);
+        assert_eq!(state.member_requirement, MemberRequirement::MinLevel { level: 4 });
+
+        // the required level has to be in the level table
+        let mut bad_level = update(30);
+        if let ExecuteMsg::UpdateConfig { member_requirement, .. } = &mut bad_level {
+            *member_requirement = Some(MemberRequirement::MinLevel { level: 9 });
+        }
+        assert!(execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), bad_level).is_err());
+    }
+
+    #[test]
+    fn level_table_management() {
+        let mut deps = mock_deps(vec![]);
+        init(&mut deps);
+        let add = |levels: Vec<Level>| ExecuteMsg::AddLevels { levels };
+
+        assert!(execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), add(vec![Level { level: 6, xp_needed: 3000 }])).is_err());
+        // gaps and xp that doesn't increase are rejected
+        assert!(execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), add(vec![Level { level: 7, xp_needed: 3000 }])).is_err());
+        assert!(execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), add(vec![Level { level: 6, xp_needed: 2000 }])).is_err());
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), add(vec![Level { level: 6, xp_needed: 3000 }])).unwrap();
+
+        let xp: u32 = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetXpForLevel { level: 6 }).unwrap()).unwrap();
+        assert_eq!(xp, 3000);
+
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), ExecuteMsg::RemoveLevels { from_level: 5 }).unwrap();
+        let levels: Vec<Level> = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetLevels {}).unwrap()).unwrap();
+        assert_eq!(levels.len(), 4);
+        assert_eq!(levels.last(), Some(&Level { level: 4, xp_needed: 1000 }));
+        assert!(query(deps.as_ref(), mock_env(), QueryMsg::GetXpForLevel { level: 6 }).is_err());
+
+        // xp past the last level no longer panics
+        assert_eq!(level_for_xp(&levels, 50), 1);
+        assert_eq!(level_for_xp(&levels, 464), 3);
+        assert_eq!(level_for_xp(&levels, 99999), 4);
+    }
+
+    #[test]
+    fn rank_import_and_removal() {
+        let mut deps = mock_deps(vec![]);
+        init(&mut deps);
+        let set = |rank: u16| ExecuteMsg::SetRanks {
+            ranks: vec![Rank { token_id: "13".to_string(), rank: 40 }, Rank { token_id: "14".to_string(), rank }],
+        };
+
+        assert!(execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), set(50)).is_err());
+        assert!(execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), set(0)).is_err());
+        assert!(execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), set(101)).is_err());
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), set(50)).unwrap();
+
+        let rank: Option<u16> = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetRank { token_id: "14".to_string() }).unwrap()).unwrap();
+        assert_eq!(rank, Some(50));
+        let ranks: Vec<Rank> = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetRanks { start_page: 1, page_size: 2 }).unwrap()).unwrap();
+        assert_eq!(ranks, vec![Rank { token_id: "12".to_string(), rank: 30 }, Rank { token_id: "13".to_string(), rank: 40 }]);
+
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), ExecuteMsg::RemoveRanks { token_ids: vec!["14".to_string()] }).unwrap();
+        let num_ranks: u32 = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetNumRanks {}).unwrap()).unwrap();
+        assert_eq!(num_ranks, 4);
+        assert_eq!(RANK_STORE.get(&deps.storage, &"14".to_string()), None);
+    }
+
+    #[test]
+    fn join_pack_uses_configured_trait_names() {
+        let mut deps = mock_deps(vec![
+            ("1", dossier(
+                metadata(&[("Leader", "Yes"), ("Experience", "500"), ("Level", "3"), ("Size", "0")], &["alpha1"]),
+                metadata(&[], &[]),
+            )),
+            ("10", dossier(
+                metadata(&[("Fur", "Grey"), ("Experience", "600")], &["pub10"]),
+                metadata(&[], &["priv10"]),
+            )),
+            ("11", dossier(
+                metadata(&[("Leader", "Yes"), ("Experience", "600")], &["pub11"]),
+                metadata(&[], &["priv11"]),
+            )),
+        ]);
+        init(&mut deps);
+
+        let mut update = ExecuteMsg::UpdateConfig {
+            receiving_address: None,
+            pack_max: None,
+            collection_size: None,
+            level_cap: None,
+            is_payment_needed: None,
+            nft_contract: None,
+            member_requirement: None,
+            trait_names: Some(TraitNames {
+                alpha: "Leader".to_string(),
+                xp: "Experience".to_string(),
+                level: "Level".to_string(),
+                pack: "Size".to_string(),
+                pack_rank: "Level".to_string(),
+            }),
+            pack_rank_rule: None,
+            is_rename_payment_needed: None,
+            unique_pack_names: None,
+        };
+        assert!(execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), update.clone()).is_err());
+        if let ExecuteMsg::UpdateConfig { trait_names: Some(trait_names), .. } = &mut update {
+            trait_names.pack_rank = "Power".to_string();
+        }
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), update).unwrap();
+
+        // the configured alpha trait is what marks an alpha
+        let err = batch_receive(&mut deps, NFT_CONTRACT, &["1", "11"], &build_msg()).unwrap_err();
+        assert_eq!(err, ContractError::CombineAlphas { token_id: "11".to_string() });
+
+        let res = batch_receive(&mut deps, NFT_CONTRACT, &["1", "10"], &build_msg()).unwrap();
+        let public_metadata = nft_msgs(&res).into_iter().find_map(|x| match x {
+            MockNftHandle::SetMetadata { public_metadata, .. } => Some(public_metadata),
+            _ => None,
+        }).unwrap();
+        assert_eq!(trait_value(&public_metadata, "Experience"), Some("1100".to_string()));
+        assert_eq!(trait_value(&public_metadata, "Level"), Some("4".to_string()));
+        assert_eq!(trait_value(&public_metadata, "Size"), Some("1".to_string()));
+        assert_eq!(trait_value(&public_metadata, "Power"), Some("90".to_string()));
+        for default_name in ["XP", "LVL", "Pack", "Pack Rank"] {
+            assert_eq!(trait_value(&public_metadata, default_name), None);
+        }
+    }
+
+    #[test]
+    fn refund_credits_returns_escrowed_payment() {
+        let mut deps = mock_deps(vec![]);
+        init(&mut deps);
+        enable_payments(&mut deps);
+        let alice_raw = deps.api.addr_canonicalize(ALICE).unwrap();
+        pay(&mut deps, 3, 30).unwrap();
+
+        let (payment_contract, price) = use_build_credit(&mut deps.storage, &alice_raw, CreditKind::Build).unwrap();
+        assert_eq!(payment_contract.unwrap().address, Addr::unchecked("snip20"));
+        assert_eq!(price, Uint128::from(10u128));
+
+        assert!(execute(deps.as_mut(), mock_env(), mock_info("bob", &[]), ExecuteMsg::AdminRefundCredits {
+            address: Addr::unchecked(ALICE),
+        }).is_err());
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), ExecuteMsg::RefundCredits {}).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        match &res.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, .. }) => {
+                assert_eq!(contract_addr, "snip20");
+                let transfer: MockSnip20Handle = from_binary(msg).unwrap();
+                let MockSnip20Handle::Transfer { recipient, amount } = transfer;
+                assert_eq!(recipient, ALICE);
+                assert_eq!(amount, Uint128::from(20u128));
+            }
+            _ => panic!("unexpected message"),
+        }
+        assert!(BUILD_CREDIT_STORE.get(&deps.storage, &alice_raw).is_none());
+        assert!(execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), ExecuteMsg::RefundCredits {}).is_err());
+    }
+
+    #[test]
+    fn refund_credits_use_updated_code_hash() {
+        let mut deps = mock_deps(vec![]);
+        init(&mut deps);
+        enable_payments(&mut deps);
+        pay(&mut deps, 2, 20).unwrap();
+
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), ExecuteMsg::UpdatePayment {
+            payment_name: "sSCRT".to_string(),
+            payment_needed: None,
+            rename_price: None,
+            code_hash: Some("new_snip_hash".to_string()),
+        }).unwrap();
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), ExecuteMsg::RefundCredits {}).unwrap();
+        match &res.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, code_hash, .. }) => {
+                assert_eq!(contract_addr, "snip20");
+                assert_eq!(code_hash, "new_snip_hash");
+            }
+            _ => panic!("unexpected message"),
+        }
+    }
+
+    #[test]
+    fn leaderboard_follows_pack_changes() {
+        let mut deps = transfer_deps();
+        let leaderboard = |deps: &MockDeps, order_by: LeaderboardOrder| -> Vec<(u32, String)> {
+            let entries: Vec<LeaderboardEntry> = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetLeaderboard {
+                start: 0,
+                limit: 10,
+                order_by,
+            }).unwrap()).unwrap();
+            entries.into_iter().map(|x| (x.position, x.pack.token_id)).collect()
+        };
+
+        assert_eq!(leaderboard(&deps, LeaderboardOrder::PackRank), vec![(1, "1".to_string())]);
+
+        batch_receive(&mut deps, NFT_CONTRACT, &["1", "2"], &transfer_msg_for("10")).unwrap();
+        assert_eq!(leaderboard(&deps, LeaderboardOrder::PackRank), vec![(1, "2".to_string()), (2, "1".to_string())]);
+        assert_eq!(leaderboard(&deps, LeaderboardOrder::PackCount), vec![(1, "1".to_string()), (2, "2".to_string())]);
+
+        let position: PackPositionResponse = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetPackPosition {
+            main_token_id: "1".to_string(),
+        }).unwrap()).unwrap();
+        assert_eq!(position, PackPositionResponse { pack_rank_position: Some(2), pack_count_position: Some(1) });
+
+        // packs saved before the indices existed are picked up by a reindex
+        PACK_MAIN_STORE.insert(&mut deps.storage, &"3".to_string(), &PackMain {
+            token_id: "3".to_string(),
+            pack_rank: 85,
+            pack_count: 3,
+            name: "Old Pack".to_string(),
+        }).unwrap();
+        let reindex = ExecuteMsg::ReindexPacks { start_page: 0, page_size: 10 };
+        assert!(execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), reindex.clone()).is_err());
+        let res = execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), reindex).unwrap();
+        assert_eq!(res.attributes[0].value, "3");
+        assert_eq!(leaderboard(&deps, LeaderboardOrder::PackRank), vec![(1, "2".to_string()), (2, "3".to_string()), (3, "1".to_string())]);
+        assert_eq!(leaderboard(&deps, LeaderboardOrder::PackCount)[0], (1, "3".to_string()));
+
+        // a page starts part way through the index
+        let entries: Vec<LeaderboardEntry> = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetLeaderboard {
+            start: 1,
+            limit: 1,
+            order_by: LeaderboardOrder::PackRank,
+        }).unwrap()).unwrap();
+        assert_eq!(entries.into_iter().map(|x| (x.position, x.pack.token_id)).collect::<Vec<_>>(), vec![(2, "3".to_string())]);
+        assert_eq!(PACK_RANK_SCORES_ITEM.load(&deps.storage).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn recompute_pack_ranks_applies_new_rule() {
+        let mut deps = transfer_deps();
+        let fur = |value: &str| vec![Trait {
+            display_type: None,
+            trait_type: Some("Fur".to_string()),
+            value: value.to_string(),
+            max_value: None,
+        }];
+        seed_pack(&mut deps, "1", vec![
+            PackMember { token_id: "10".to_string(), rank: 10, attributes: fur("Black") },
+            PackMember { token_id: "11".to_string(), rank: 20, attributes: fur("Grey") },
+        ]);
+        assert_eq!(PACK_MAIN_STORE.get(&deps.storage, &"1".to_string()).unwrap().pack_rank, 170);
+
+        let tier = |name: &str, max_rank: u16, score: u32| RankTier { name: name.to_string(), max_rank, score };
+        let update = |tiers: Vec<RankTier>| ExecuteMsg::UpdateConfig {
+            receiving_address: None,
+            pack_max: None,
+            collection_size: None,
+            level_cap: None,
+            is_payment_needed: None,
+            nft_contract: None,
+            member_requirement: None,
+            trait_names: None,
+            pack_rank_rule: Some(PackRankRule {
+                base: RankScore::Tiered { tiers },
+                trait_bonuses: vec![TraitBonus { trait_type: "Fur".to_string(), value: "Grey".to_string(), bonus: 5 }],
+            }),
+            is_rename_payment_needed: None,
+            unique_pack_names: None,
+        };
+        let err = execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), update(vec![tier("legendary", 10, 50), tier("epic", 10, 20)])).unwrap_err();
+        assert_eq!(err, ContractError::RankTiersNotIncreasing { name: "epic".to_string() });
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), update(vec![tier("legendary", 10, 50), tier("epic", 15, 20)])).unwrap();
+
+        let recompute = ExecuteMsg::RecomputePackRanks { start_page: 0, page_size: 10 };
+        assert!(execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), recompute.clone()).is_err());
+        let res = execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), recompute.clone()).unwrap();
+        // legendary member 10 scores 50, member 11 is past the last tier but has the bonus
+        assert_eq!(PACK_MAIN_STORE.get(&deps.storage, &"1".to_string()).unwrap().pack_rank, 55);
+        // the alpha isn't held by the contract, so its metadata is left for the next time it's sent in
+        assert!(res.messages.is_empty());
+        assert_eq!(res.attributes[0].value, "1");
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), recompute.clone()).unwrap();
+        assert_eq!(res.attributes[0].value, "0");
+
+        // rank fixes made after the members joined are picked up
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), ExecuteMsg::SetRanks {
+            ranks: vec![Rank { token_id: "11".to_string(), rank: 12 }],
+        }).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), recompute).unwrap();
+        assert_eq!(PACK_MEMBER_STORE.get(&deps.storage, &"1".to_string()).unwrap()[1].rank, 12);
+        assert_eq!(PACK_MAIN_STORE.get(&deps.storage, &"1".to_string()).unwrap().pack_rank, 75);
+    }
+
+    #[test]
+    fn pack_rank_tops_out_instead_of_overflowing() {
+        let mut deps = mock_deps(vec![]);
+        init(&mut deps);
+        let mut state = CONFIG_ITEM.load(&deps.storage).unwrap();
+        state.pack_rank_rule = PackRankRule {
+            base: RankScore::Flat { value: u32::MAX - 1 },
+            trait_bonuses: vec![
+                TraitBonus { trait_type: "Fur".to_string(), value: "Grey".to_string(), bonus: u32::MAX },
+                TraitBonus { trait_type: "Fur".to_string(), value: "Grey".to_string(), bonus: 5 },
+            ],
+        };
+        let grey = PackMember {
+            token_id: "10".to_string(),
+            rank: 10,
+            attributes: vec![Trait {
+                display_type: None,
+                trait_type: Some("Fur".to_string()),
+                value: "Grey".to_string(),
+                max_value: None,
+            }],
+        };
+        assert_eq!(member_score(&state, &grey), u32::MAX);
+        let plain = PackMember { token_id: "11".to_string(), rank: 20, attributes: vec![] };
+        assert_eq!(calculate_pack_rank(&state, &[plain.clone()]), u32::MAX - 1);
+        assert_eq!(calculate_pack_rank(&state, &[plain, grey]), u32::MAX);
+    }
+
+    #[test]
+    fn join_pack_rejects_duplicates_and_tokens_not_received() {
+        let mut deps = mock_deps(vec![
+            ("1", alpha_dossier()),
+            ("10", wolf_dossier("500", "10")),
+        ]);
+        init(&mut deps);
+        let err = batch_receive(&mut deps, NFT_CONTRACT, &["1", "10", "10"], &build_msg()).unwrap_err();
+        assert_eq!(err, ContractError::DuplicateToken { token_id: "10".to_string() });
+        let err = batch_receive(&mut deps, NFT_CONTRACT, &["1", "10", "1"], &build_msg()).unwrap_err();
+        assert_eq!(err, ContractError::DuplicateToken { token_id: "1".to_string() });
+        // the alpha alone has no members to add
+        let err = batch_receive(&mut deps, NFT_CONTRACT, &["1"], &build_msg()).unwrap_err();
+        assert_eq!(err, ContractError::EmptyTokenList {});
+
+        let mut not_received = alpha_dossier();
+        not_received.owner = Some(ALICE.to_string());
+        let err = join_with(not_received, wolf_dossier("500", "10")).unwrap_err();
+        assert_eq!(err, ContractError::TokenNotReceived { token_id: "1".to_string() });
+        let mut not_received = wolf_dossier("500", "10");
+        not_received.owner = Some(ALICE.to_string());
+        let err = join_with(alpha_dossier(), not_received).unwrap_err();
+        assert_eq!(err, ContractError::TokenNotReceived { token_id: "10".to_string() });
+    }
+
+    // compressed public keys of the secp256k1 private keys 1 and 2
+    const PERMIT_PUBKEY: &str = "Anm+Zn753LusVaBilc6HCwcCm/zbLc4o2VnygVsW+BeY";
+    const OTHER_PUBKEY: &str = "AsYEf5RB7X1tMEVAbpXAfNhcd45LjO88p6usCblccJ7l";
+    const PERMIT_ADDRESS: &str = "secret1w508d6qejxtdg4y5r3zarvary0c5xw7kccrnjy";
+
+    fn private_key(key: u8) -> Vec<u8> {
+        let mut private_key = vec![0u8; 32];
+        private_key[31] = key;
+        private_key
+    }
+
+    fn signed_permit(
+        deps: &MockDeps,
+        key: u8,
+        pub_key: &str,
+        allowed_token: &str,
+        permissions: Vec<TokenPermissions>,
+    ) -> Permit {
+        let params = PermitParams {
+            allowed_tokens: vec![allowed_token.to_string()],
+            permit_name: "pack history".to_string(),
+            chain_id: "secret-4".to_string(),
+            permissions,
+        };
+        let signed_bytes = to_binary(&SignedPermit::from_params(&params)).unwrap();
+        let signature = deps.api.secp256k1_sign(signed_bytes.as_slice(), &private_key(key)).unwrap();
+        Permit {
+            params,
+            signature: PermitSignature {
+                pub_key: PubKey {
+                    r#type: "tendermint/PubKeySecp256k1".to_string(),
+                    value: Binary::from_base64(pub_key).unwrap(),
+                },
+                signature: Binary::from(signature),
+            },
+        }
+    }
+
+    #[test]
+    fn permit_queries_validate_signed_permits() {
+        let mut deps = mock_deps(vec![]);
+        init(&mut deps);
+        let contract = mock_env().contract.address.to_string();
+        let permit_raw = deps.api.addr_canonicalize(PERMIT_ADDRESS).unwrap();
+        BUILD_CREDIT_STORE.insert(&mut deps.storage, &permit_raw, &vec![BuildCredit {
+            payment_contract: Some(ContractInfo { code_hash: "snip_hash".to_string(), address: Addr::unchecked("snip20") }),
+            price: Uint128::from(10u128),
+            quantity: 2,
+            kind: CreditKind::Build,
+        }, BuildCredit {
+            payment_contract: Some(ContractInfo { code_hash: "snip_hash".to_string(), address: Addr::unchecked("snip20") }),
+            price: Uint128::from(4u128),
+            quantity: 5,
+            kind: CreditKind::Rename,
+        }]).unwrap();
+
+        let credits = |deps: &MockDeps, permit: Permit| query(deps.as_ref(), mock_env(), QueryMsg::GetBuildCredits { permit });
+
+        let permit = signed_permit(&deps, 1, PERMIT_PUBKEY, &contract, vec![TokenPermissions::Owner]);
+        let num: u32 = from_binary(&credits(&deps, permit.clone()).unwrap()).unwrap();
+        assert_eq!(num, 2);
+        let num: u32 = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetNumUserHistory { permit }).unwrap()).unwrap();
+        assert_eq!(num, 0);
+
+        // signed by a different key than the one in the permit
+        assert!(credits(&deps, signed_permit(&deps, 2, PERMIT_PUBKEY, &contract, vec![TokenPermissions::Owner])).is_err());
+        // valid signature of the second key, which has no credits
+        let num: u32 = from_binary(&credits(&deps, signed_permit(&deps, 2, OTHER_PUBKEY, &contract, vec![TokenPermissions::Owner])).unwrap()).unwrap();
+        assert_eq!(num, 0);
+        assert!(credits(&deps, signed_permit(&deps, 1, PERMIT_PUBKEY, "other_contract", vec![TokenPermissions::Owner])).is_err());
+        assert!(credits(&deps, signed_permit(&deps, 1, PERMIT_PUBKEY, &contract, vec![TokenPermissions::History])).is_err());
+
+        let permit = signed_permit(&deps, 1, PERMIT_PUBKEY, &contract, vec![TokenPermissions::Owner]);
+        execute(deps.as_mut(), mock_env(), mock_info(PERMIT_ADDRESS, &[]), ExecuteMsg::RevokePermit {
+            permit_name: "pack history".to_string(),
+        }).unwrap();
+        assert!(credits(&deps, permit).is_err());
+    }
+
+    #[test]
+    fn history_round_trips_and_migrates() {
+        let mut deps = mock_deps(vec![
+            ("1", alpha_dossier()),
+            ("10", wolf_dossier("500", "10")),
+        ]);
+        init(&mut deps);
+        let contract = mock_env().contract.address.to_string();
+
+        execute(deps.as_mut(), mock_env(), mock_info(NFT_CONTRACT, &[]), ExecuteMsg::BatchReceiveNft {
+            from: Addr::unchecked(PERMIT_ADDRESS),
+            token_ids: vec!["1".to_string(), "10".to_string()],
+            msg: Some(to_binary(&build_msg()).unwrap()),
+        }).unwrap();
+
+        let history = |deps: &MockDeps| -> Vec<HistoryToken> {
+            let permit = signed_permit(deps, 1, PERMIT_PUBKEY, &contract, vec![TokenPermissions::Owner]);
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetUserHistory { permit, start_page: 0, page_size: 10 }).unwrap()).unwrap()
+        };
+        let built = HistoryToken {
+            wolf_main_token_id: "1".to_string(),
+            pack_member_token_ids: vec!["10".to_string()],
+            pack_build_date: Some(mock_env().block.time.seconds()),
+            kind: HistoryKind::Build,
+        };
+        assert_eq!(history(&deps), vec![built.clone()]);
+
+        // an entry written under the human address before the fix
+        LEGACY_HISTORY_STORE.add_suffix(PERMIT_ADDRESS.as_bytes()).push(&mut deps.storage, &LegacyHistoryToken {
+            wolf_main_token_id: "2".to_string(),
+            pack_member_token_ids: vec!["12".to_string()],
+            pack_build_date: Some(1),
+        }).unwrap();
+        let old = HistoryToken {
+            wolf_main_token_id: "2".to_string(),
+            pack_member_token_ids: vec!["12".to_string()],
+            pack_build_date: Some(1),
+            kind: HistoryKind::Build,
+        };
+
+        let migrate = ExecuteMsg::MigrateHistory { addresses: vec![Addr::unchecked(PERMIT_ADDRESS), Addr::unchecked(ALICE)] };
+        assert!(execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), migrate.clone()).is_err());
+        let res = execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), migrate).unwrap();
+        assert_eq!(res.attributes[0].value, "1");
+        assert_eq!(history(&deps), vec![old, built]);
+        assert_eq!(HISTORY_STORE.add_suffix(PERMIT_ADDRESS.as_bytes()).get_len(&deps.storage).unwrap(), 0);
+    }
+
+    #[test]
+    fn migrate_converts_old_instances() {
+        let mut deps = mock_deps(vec![]);
+        let alice_raw = deps.api.addr_canonicalize(ALICE).unwrap();
+        let payment = LegacyPaymentContractInfo {
+            code_hash: "snip_hash".to_string(),
+            address: Addr::unchecked("snip20"),
+            payment_needed: Uint128::from(10u128),
+            name: "sSCRT".to_string(),
+        };
+        LEGACY_CONFIG_ITEM.save(&mut deps.storage, &LegacyState {
+            owner: Addr::unchecked(OWNER),
+            nft_contract: ContractInfo { code_hash: "nft_hash".to_string(), address: Addr::unchecked(NFT_CONTRACT) },
+            is_payment_needed: true,
+            valid_payments: Some(vec![payment.clone()]),
+            viewing_key: Some("key".to_string()),
+            receiving_address: Addr::unchecked("treasury"),
+            total_burned: 7,
+            pack_max: 10,
+            collection_size: 100,
+            level_cap: 5,
+        }).unwrap();
+        LEGACY_PAID_ADDRESSES_ITEM.save(&mut deps.storage, &vec![alice_raw.clone()]).unwrap();
+        LEGACY_HISTORY_STORE.add_suffix(ALICE.as_bytes()).push(&mut deps.storage, &LegacyHistoryToken {
+            wolf_main_token_id: "1".to_string(),
+            pack_member_token_ids: vec!["10".to_string()],
+            pack_build_date: Some(1),
+        }).unwrap();
+
+        let msg = MigrateMsg { entropy: "entropy".to_string(), history_addresses: vec![Addr::unchecked(ALICE)] };
+        let res = migrate(deps.as_mut(), mock_env(), msg.clone()).unwrap();
+        assert_eq!(res.attributes[0].value, "1");
+
+        let state = CONFIG_ITEM.load(&deps.storage).unwrap();
+        assert_eq!((state.total_burned, state.valid_payments.clone()), (7, Some(vec![PaymentContractInfo {
+            code_hash: "snip_hash".to_string(),
+            address: Addr::unchecked("snip20"),
+            payment_needed: Uint128::from(10u128),
+            rename_price: Uint128::from(10u128),
+            name: "sSCRT".to_string(),
+        }])));
+        assert_eq!(state.trait_names, TraitNames::default());
+        assert!(LEGACY_PAID_ADDRESSES_ITEM.may_load(&deps.storage).unwrap().is_none());
+        assert_eq!(HISTORY_STORE.add_suffix(alice_raw.as_slice()).get_len(&deps.storage).unwrap(), 1);
+        execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), ExecuteMsg::CreateViewingKey { entropy: "e".to_string(), padding: None }).unwrap();
+
+        // the paid build became a credit with nothing in escrow, which a refund leaves alone
+        let credits = BUILD_CREDIT_STORE.get(&deps.storage, &alice_raw).unwrap();
+        assert_eq!((credits[0].payment_contract.as_ref(), credits[0].price, credits[0].quantity), (None, Uint128::zero(), 1));
+        let err = execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), ExecuteMsg::RefundCredits {}).unwrap_err();
+        assert_eq!(err, ContractError::NoBuildCredits {});
+        assert_eq!(pay_with_build_credit(&mut deps.storage, &state, &alice_raw, CreditKind::Build).unwrap(), None);
+        assert!(BUILD_CREDIT_STORE.get(&deps.storage, &alice_raw).is_none());
+
+        // a second migrate leaves the converted state as it is
+        migrate(deps.as_mut(), mock_env(), msg).unwrap();
+        assert_eq!(CONFIG_ITEM.load(&deps.storage).unwrap(), state);
+    }
+
+    #[test]
+    fn viewing_keys_authenticate_user_queries() {
+        let mut deps = mock_deps(vec![]);
+        init(&mut deps);
+        let alice_raw = deps.api.addr_canonicalize(ALICE).unwrap();
+        BUILD_CREDIT_STORE.insert(&mut deps.storage, &alice_raw, &vec![BuildCredit {
+            payment_contract: Some(ContractInfo { code_hash: "snip_hash".to_string(), address: Addr::unchecked("snip20") }),
+            price: Uint128::from(10u128),
+            quantity: 3,
+            kind: CreditKind::Build,
+        }]).unwrap();
+        let credits = |deps: &MockDeps, key: &str| query(deps.as_ref(), mock_env(), QueryMsg::GetBuildCreditsWithKey {
+            address: Addr::unchecked(ALICE),
+            key: key.to_string(),
+        });
+
+        let res = execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), ExecuteMsg::CreateViewingKey {
+            entropy: "wolves".to_string(),
+            padding: None,
+        }).unwrap();
+        let created: ViewingKeyResponse = from_binary(&res.data.unwrap()).unwrap();
+        let num: u32 = from_binary(&credits(&deps, &created.key).unwrap()).unwrap();
+        assert_eq!(num, 3);
+        let num: u32 = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetNumUserHistoryWithKey {
+            address: Addr::unchecked(ALICE),
+            key: created.key.to_string(),
+        }).unwrap()).unwrap();
+        assert_eq!(num, 0);
+        let history: Vec<HistoryToken> = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetUserHistoryWithKey {
+            address: Addr::unchecked(ALICE),
+            key: created.key.to_string(),
+            start_page: 0,
+            page_size: 10,
+        }).unwrap()).unwrap();
+        assert!(history.is_empty());
+
+        execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), ExecuteMsg::SetViewingKey {
+            key: "my key".to_string(),
+            padding: None,
+        }).unwrap();
+        assert!(credits(&deps, &created.key).is_err());
+        assert!(credits(&deps, "wrong key").is_err());
+        let num: u32 = from_binary(&credits(&deps, "my key").unwrap()).unwrap();
+        assert_eq!(num, 3);
+        // a key only unlocks the address it was set for
+        assert!(query(deps.as_ref(), mock_env(), QueryMsg::GetBuildCreditsWithKey {
+            address: Addr::unchecked(OWNER),
+            key: "my key".to_string(),
+        }).is_err());
+    }
+
+    fn disband_msg(keep_xp: bool) -> HandleReceiveMsg {
+        HandleReceiveMsg::ReceiveDisbandPack {
+            disband: PackDisbandMsg { main_token_id: "1".to_string(), keep_xp },
+        }
+    }
+
+    fn disband_deps() -> MockDeps {
+        let mut deps = transfer_deps();
+        let xp = |value: &str| vec![Trait {
+            display_type: None,
+            trait_type: Some("XP".to_string()),
+            value: value.to_string(),
+            max_value: None,
+        }];
+        seed_pack(&mut deps, "1", vec![
+            PackMember { token_id: "10".to_string(), rank: 10, attributes: xp("100") },
+            PackMember { token_id: "11".to_string(), rank: 20, attributes: xp("200") },
+        ]);
+        deps
+    }
+
+    #[test]
+    fn disband_pack_releases_alpha() {
+        let mut deps = disband_deps();
+
+        assert!(PACK_NAME_STORE.get(&deps.storage, &"moon howlers".to_string()).is_some());
+        let res = batch_receive(&mut deps, NFT_CONTRACT, &["1"], &disband_msg(false)).unwrap();
+        assert!(PACK_MEMBER_STORE.get(&deps.storage, &"1".to_string()).is_none());
+        assert!(PACK_NAME_STORE.get(&deps.storage, &"moon howlers".to_string()).is_none());
+        assert!(PACK_MAIN_STORE.get(&deps.storage, &"1".to_string()).is_none());
+        let leaderboard: Vec<LeaderboardEntry> = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetLeaderboard {
+            start: 0,
+            limit: 10,
+            order_by: LeaderboardOrder::PackRank,
+        }).unwrap()).unwrap();
+        assert!(leaderboard.is_empty());
+        assert_eq!(res.attributes[0].value, "2");
+
+        let msgs = nft_msgs(&res);
+        match &msgs[0] {
+            MockNftHandle::SetMetadata { token_id, public_metadata, private_metadata } => {
+                assert_eq!(token_id, "1");
+                assert_eq!(trait_value(public_metadata, "Pack"), Some("0".to_string()));
+                assert_eq!(trait_value(public_metadata, "Pack Rank"), Some("0".to_string()));
+                assert_eq!(trait_value(public_metadata, "XP"), Some("200".to_string()));
+                assert_eq!(trait_value(public_metadata, "LVL"), Some("2".to_string()));
+                assert_eq!(media_urls(public_metadata), vec!["alpha1"]);
+                assert!(media_urls(private_metadata).is_empty());
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        match &msgs[1] {
+            MockNftHandle::TransferNft { recipient, token_id } => {
+                assert_eq!(recipient, ALICE);
+                assert_eq!(token_id, "1");
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+
+        let alice_raw = deps.api.addr_canonicalize(ALICE).unwrap();
+        let history = HISTORY_STORE.add_suffix(alice_raw.as_slice()).get_at(&deps.storage, 0).unwrap();
+        assert_eq!(history.kind, HistoryKind::Disband);
+        assert_eq!(history.pack_member_token_ids, vec!["10", "11"]);
+
+        let err = batch_receive(&mut deps, NFT_CONTRACT, &["1"], &disband_msg(false)).unwrap_err();
+        assert_eq!(err, ContractError::NoPack { token_id: "1".to_string() });
+    }
+
+    #[test]
+    fn disband_pack_can_keep_xp() {
+        let mut deps = disband_deps();
+        let err = batch_receive(&mut deps, NFT_CONTRACT, &["1", "2"], &disband_msg(true)).unwrap_err();
+        assert_eq!(err, ContractError::UnexpectedToken { token_id: "2".to_string() });
+
+        let res = batch_receive(&mut deps, NFT_CONTRACT, &["1"], &disband_msg(true)).unwrap();
+        assert!(res.attributes.is_empty());
+        match &nft_msgs(&res)[0] {
+            MockNftHandle::SetMetadata { public_metadata, .. } => {
+                assert_eq!(trait_value(public_metadata, "XP"), Some("500".to_string()));
+                assert_eq!(trait_value(public_metadata, "LVL"), Some("3".to_string()));
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    fn merge_msg(burn_absorbed: bool) -> HandleReceiveMsg {
+        HandleReceiveMsg::ReceiveMergePacks {
+            merge: PackMergeMsg { main_token_id: "1".to_string(), absorbed_token_id: "2".to_string(), burn_absorbed },
+        }
+    }
+
+    fn merge_deps() -> MockDeps {
+        let mut deps = mock_deps(vec![
+            ("1", dossier(
+                metadata(&[("Alpha", "Yes"), ("XP", "500"), ("LVL", "3"), ("Pack", "2"), ("Pack Rank", "170")], &["alpha1", "pub10", "pub11"]),
+                metadata(&[], &["priv10", "priv11"]),
+            )),
+            ("2", dossier(
+                metadata(&[("Alpha", "Yes"), ("XP", "800"), ("LVL", "3"), ("Pack", "1"), ("Pack Rank", "70")], &["alpha2", "pub12"]),
+                metadata(&[], &["priv12"]),
+            )),
+        ]);
+        init(&mut deps);
+        seed_pack(&mut deps, "1", vec![member("10", 10), member("11", 20)]);
+        let xp = vec![Trait { display_type: None, trait_type: Some("XP".to_string()), value: "300".to_string(), max_value: None }];
+        seed_pack(&mut deps, "2", vec![PackMember { token_id: "12".to_string(), rank: 30, attributes: xp }]);
+        deps
+    }
+
+    #[test]
+    fn merge_packs_demotes_absorbed_alpha() {
+        let mut deps = merge_deps();
+
+        let res = batch_receive(&mut deps, NFT_CONTRACT, &["1", "2"], &merge_msg(false)).unwrap();
+        let members = PACK_MEMBER_STORE.get(&deps.storage, &"1".to_string()).unwrap();
+        assert_eq!(members.iter().map(|x| x.token_id.as_str()).collect::<Vec<&str>>(), vec!["10", "11", "12"]);
+        assert!(PACK_MEMBER_STORE.get(&deps.storage, &"2".to_string()).is_none());
+        assert_eq!(PACK_MAIN_STORE.get_len(&deps.storage).unwrap(), 1);
+        let pack = PACK_MAIN_STORE.get(&deps.storage, &"1".to_string()).unwrap();
+        assert_eq!((pack.pack_count, pack.pack_rank), (3, 240));
+
+        let msgs = nft_msgs(&res);
+        assert_eq!(msgs.len(), 3);
+        match &msgs[0] {
+            MockNftHandle::SetMetadata { token_id, public_metadata, private_metadata } => {
+                assert_eq!(token_id, "1");
+                assert_eq!(trait_value(public_metadata, "XP"), Some("800".to_string()));
+                assert_eq!(trait_value(public_metadata, "Pack"), Some("3".to_string()));
+                assert_eq!(trait_value(public_metadata, "Pack Rank"), Some("240".to_string()));
+                assert_eq!(media_urls(public_metadata), vec!["alpha1", "pub10", "pub11", "pub12"]);
+                assert_eq!(media_urls(private_metadata), vec!["priv10", "priv11", "priv12"]);
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        match &msgs[1] {
+            MockNftHandle::SetMetadata { token_id, public_metadata, private_metadata } => {
+                assert_eq!(token_id, "2");
+                assert_eq!(trait_value(public_metadata, "XP"), Some("500".to_string()));
+                assert_eq!(trait_value(public_metadata, "Pack"), Some("0".to_string()));
+                assert_eq!(trait_value(public_metadata, "Pack Rank"), Some("0".to_string()));
+                assert_eq!(media_urls(public_metadata), vec!["alpha2"]);
+                assert!(media_urls(private_metadata).is_empty());
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        match &msgs[2] {
+            MockNftHandle::BatchTransferNft { transfers } => assert_eq!(transfers[0].token_ids, vec!["1", "2"]),
+            other => panic!("unexpected message {:?}", other),
+        }
+
+        let alice_raw = deps.api.addr_canonicalize(ALICE).unwrap();
+        let history = HISTORY_STORE.add_suffix(alice_raw.as_slice()).get_at(&deps.storage, 0).unwrap();
+        assert_eq!(history.kind, HistoryKind::Merge);
+        assert_eq!(history.pack_member_token_ids, vec!["12"]);
+    }
+
+    #[test]
+    fn merge_packs_burns_absorbed_alpha() {
+        let mut deps = merge_deps();
+        let err = batch_receive(&mut deps, NFT_CONTRACT, &["1"], &merge_msg(true)).unwrap_err();
+        assert_eq!(err, ContractError::TokenNotInList { token_id: "2".to_string() });
+        let mut state = CONFIG_ITEM.load(&deps.storage).unwrap();
+        state.pack_max = 2;
+        CONFIG_ITEM.save(&mut deps.storage, &state).unwrap();
+        let err = batch_receive(&mut deps, NFT_CONTRACT, &["1", "2"], &merge_msg(true)).unwrap_err();
+        assert_eq!(err, ContractError::PackFull { main_token_id: "1".to_string(), slots_left: 0 });
+        state.pack_max = 10;
+        CONFIG_ITEM.save(&mut deps.storage, &state).unwrap();
+
+        let res = batch_receive(&mut deps, NFT_CONTRACT, &["1", "2"], &merge_msg(true)).unwrap();
+        assert_eq!(res.attributes[0].value, "4");
+        assert_eq!(CONFIG_ITEM.load(&deps.storage).unwrap().total_burned, 1);
+        let msgs = nft_msgs(&res);
+        assert_eq!(msgs.len(), 3);
+        match &msgs[0] {
+            MockNftHandle::SetMetadata { public_metadata, .. } => {
+                assert_eq!(trait_value(public_metadata, "XP"), Some("1300".to_string()));
+                assert_eq!(trait_value(public_metadata, "LVL"), Some("4".to_string()));
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+        match &msgs[1] {
+            MockNftHandle::BatchBurnNft { burns } => assert_eq!(burns[0].token_ids, vec!["2"]),
+            other => panic!("unexpected message {:?}", other),
+        }
+        match &msgs[2] {
+            MockNftHandle::BatchTransferNft { transfers } => assert_eq!(transfers[0].token_ids, vec!["1"]),
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+
+    fn rename_msg(name: &str) -> HandleReceiveMsg {
+        HandleReceiveMsg::ReceiveRenamePack {
+            rename: PackRenameMsg { main_token_id: "1".to_string(), name: name.to_string() },
+        }
+    }
+
+    #[test]
+    fn rename_pack_validates_and_updates_name() {
+        let mut deps = transfer_deps();
+
+        let res = batch_receive(&mut deps, NFT_CONTRACT, &["1"], &rename_msg("  Night Stalkers ")).unwrap();
+        assert_eq!(PACK_MAIN_STORE.get(&deps.storage, &"1".to_string()).unwrap().name, "Night Stalkers");
+        let msgs = nft_msgs(&res);
+        assert_eq!(msgs.len(), 2);
+        match &msgs[0] {
+            MockNftHandle::SetMetadata { token_id, public_metadata, private_metadata } => {
+                assert_eq!(token_id, "1");
+                assert_eq!(public_metadata.as_ref().unwrap().extension.as_ref().unwrap().name, Some("Night Stalkers".to_string()));
+                assert_eq!(trait_value(public_metadata, "Pack"), Some("2".to_string()));
+                assert_eq!(trait_value(public_metadata, "Pack Rank"), Some("170".to_string()));
+                assert!(private_metadata.is_none());
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+
+        let length_err = ContractError::InvalidPackNameLength { min: MIN_PACK_NAME_LENGTH, max: MAX_PACK_NAME_LENGTH };
+        assert_eq!(batch_receive(&mut deps, NFT_CONTRACT, &["1"], &rename_msg("ab")).unwrap_err(), length_err);
+        assert_eq!(batch_receive(&mut deps, NFT_CONTRACT, &["1"], &rename_msg(&"a".repeat(33))).unwrap_err(), length_err);
+        assert_eq!(
+            batch_receive(&mut deps, NFT_CONTRACT, &["1"], &rename_msg("Pack<script>")).unwrap_err(),
+            ContractError::InvalidPackNameChar { character: '<' }
+        );
+        assert_eq!(
+            batch_receive(&mut deps, NFT_CONTRACT, &["2"], &HandleReceiveMsg::ReceiveRenamePack {
+                rename: PackRenameMsg { main_token_id: "2".to_string(), name: "Lone Wolf".to_string() },
+            }).unwrap_err(),
+            ContractError::NoPack { token_id: "2".to_string() }
+        );
+
+        let blocklist = ExecuteMsg::SetNameBlocklist { words: vec![" Darn ".to_string()] };
+        assert!(execute(deps.as_mut(), mock_env(), mock_info(ALICE, &[]), blocklist.clone()).is_err());
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), blocklist).unwrap();
+        assert_eq!(batch_receive(&mut deps, NFT_CONTRACT, &["1"], &rename_msg("DARN Wolves")).unwrap_err(), ContractError::BlockedPackName {});
+
+        // builds keep taking the names holders already use, the rules only apply to renames
+        let mut deps = mock_deps(vec![("1", alpha_dossier()), ("10", wolf_dossier("500", "10"))]);
+        init(&mut deps);
+        batch_receive(&mut deps, NFT_CONTRACT, &["1", "10"], &HandleReceiveMsg::ReceivePackBuild {
+            pack_build: PackBuildMsg { main_token_id: "1".to_string(), name: "Loups d'Été & Co!".to_string() },
+        }).unwrap();
+        assert_eq!(PACK_MAIN_STORE.get(&deps.storage, &"1".to_string()).unwrap().name, "Loups d'Été & Co!");
+    }
+
+    #[test]
+    fn rename_pack_charges_a_rename_credit() {
+        let mut deps = transfer_deps();
+        enable_payments(&mut deps);
+        let mut state = CONFIG_ITEM.load(&deps.storage).unwrap();
+        state.is_payment_needed = false;
+        state.is_rename_payment_needed = true;
+        CONFIG_ITEM.save(&mut deps.storage, &state).unwrap();
+        let alice_raw = deps.api.addr_canonicalize(ALICE).unwrap();
+
+        // a prepaid build isn't spent on a rename
+        pay(&mut deps, 1, 10).unwrap();
+        let err = batch_receive(&mut deps, NFT_CONTRACT, &["1"], &rename_msg("Night Stalkers")).unwrap_err();
+        assert_eq!(err, ContractError::PaymentNotReceived {});
+
+        let pay_rename = |deps: &mut MockDeps, amount: u128| execute(deps.as_mut(), mock_env(), mock_info("snip20", &[]), ExecuteMsg::Receive {
+            sender: Addr::unchecked(ALICE),
+            from: Addr::unchecked(ALICE),
+            amount: Uint128::from(amount),
+            msg: Some(to_binary(&ReceiveMsg { quantity: 1, kind: CreditKind::Rename }).unwrap()),
+        });
+        assert!(pay_rename(&mut deps, 10).is_err());
+        pay_rename(&mut deps, 4).unwrap();
+
+        let res = batch_receive(&mut deps, NFT_CONTRACT, &["1"], &rename_msg("Night Stalkers")).unwrap();
+        assert_eq!(res.messages.len(), 3);
+        match &res.messages[0].msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, .. }) => {
+                assert_eq!(contract_addr, "snip20");
+                let transfer: MockSnip20Handle = from_binary(msg).unwrap();
+                let MockSnip20Handle::Transfer { recipient, amount } = transfer;
+                assert_eq!(recipient, "treasury");
+                assert_eq!(amount, Uint128::from(4u128));
+            }
+            _ => panic!("unexpected message"),
+        }
+        let credits = BUILD_CREDIT_STORE.get(&deps.storage, &alice_raw).unwrap();
+        assert_eq!(credits.len(), 1);
+        assert_eq!((credits[0].kind, credits[0].quantity), (CreditKind::Build, 1));
+    }
+
+    #[test]
+    fn unique_pack_names_use_name_index() {
+        let mut deps = mock_deps(vec![
+            ("1", alpha_dossier()),
+            ("2", dossier(
+                metadata(&[("Alpha", "Yes"), ("XP", "500"), ("LVL", "3"), ("Pack", "0")], &["alpha2"]),
+                metadata(&[], &[]),
+            )),
+            ("10", wolf_dossier("500", "10")),
+            ("11", wolf_dossier("600", "11")),
+        ]);
+        init(&mut deps);
+        let mut state = CONFIG_ITEM.load(&deps.storage).unwrap();
+        state.unique_pack_names = true;
+        CONFIG_ITEM.save(&mut deps.storage, &state).unwrap();
+        let build = |main_token_id: &str, name: &str| HandleReceiveMsg::ReceivePackBuild {
+            pack_build: PackBuildMsg { main_token_id: main_token_id.to_string(), name: name.to_string() },
+        };
+        let pack_by_name = |deps: &MockDeps, name: &str| -> Option<String> {
+            let pack: Option<PackMain> = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetPackByName {
+                name: name.to_string(),
+            }).unwrap()).unwrap();
+            pack.map(|x| x.token_id)
+        };
+
+        // builds keep the name as sent, the index ignores case and surrounding spaces
+        batch_receive(&mut deps, NFT_CONTRACT, &["1", "10"], &build("1", "Moon Howlers ")).unwrap();
+        assert_eq!(PACK_MAIN_STORE.get(&deps.storage, &"1".to_string()).unwrap().name, "Moon Howlers ");
+        assert_eq!(pack_by_name(&deps, " moon HOWLERS"), Some("1".to_string()));
+
+        let err = batch_receive(&mut deps, NFT_CONTRACT, &["2", "11"], &build("2", "MOON HOWLERS")).unwrap_err();
+        assert_eq!(err, ContractError::PackNameTaken { name: "MOON HOWLERS".to_string() });
+        let err = batch_receive(&mut deps, NFT_CONTRACT, &["2", "11"], &build("2", "  moon howlers")).unwrap_err();
+        assert_eq!(err, ContractError::PackNameTaken { name: "  moon howlers".to_string() });
+        batch_receive(&mut deps, NFT_CONTRACT, &["2", "11"], &build("2", "Night Stalkers")).unwrap();
+
+        let err = batch_receive(&mut deps, NFT_CONTRACT, &["1"], &rename_msg("night stalkers")).unwrap_err();
+        assert_eq!(err, ContractError::PackNameTaken { name: "night stalkers".to_string() });
+        batch_receive(&mut deps, NFT_CONTRACT, &["1"], &rename_msg("Sun Chasers")).unwrap();
+        assert_eq!(pack_by_name(&deps, "moon howlers"), None);
+        assert_eq!(pack_by_name(&deps, "sun chasers"), Some("1".to_string()));
+
+        // the freed name can be used again
+        batch_receive(&mut deps, NFT_CONTRACT, &["2"], &HandleReceiveMsg::ReceiveRenamePack {
+            rename: PackRenameMsg { main_token_id: "2".to_string(), name: "Moon Howlers".to_string() },
+        }).unwrap();
+        assert_eq!(pack_by_name(&deps, "moon howlers"), Some("2".to_string()));
+        assert_eq!(pack_by_name(&deps, "night stalkers"), None);
+    }
+
+    #[test]
+    fn member_index_follows_pack_changes() {
+        let mut deps = mock_deps(vec![
+            ("1", dossier(
+                metadata(&[("Alpha", "Yes"), ("XP", "500"), ("LVL", "3"), ("Pack", "2"), ("Pack Rank", "170")], &["alpha1", "pub10", "pub11"]),
+                metadata(&[], &["priv10", "priv11"]),
+            )),
+            ("2", dossier(
+                metadata(&[("Alpha", "Yes"), ("XP", "500"), ("LVL", "3"), ("Pack", "0")], &["alpha2"]),
+                metadata(&[], &[]),
+            )),
+            ("10", wolf_dossier("500", "10")),
+            ("11", wolf_dossier("600", "11")),
+        ]);
+        init(&mut deps);
+        let pack_of = |deps: &MockDeps, token_id: &str| -> Option<String> {
+            let pack: Option<PackMain> = from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetPackOfMember {
+                token_id: token_id.to_string(),
+            }).unwrap()).unwrap();
+            pack.map(|x| x.token_id)
+        };
+        assert_eq!(pack_of(&deps, "10"), None);
+
+        batch_receive(&mut deps, NFT_CONTRACT, &["1", "10", "11"], &build_msg()).unwrap();
+        assert_eq!(pack_of(&deps, "10"), Some("1".to_string()));
+        assert_eq!(pack_of(&deps, "11"), Some("1".to_string()));
+
+        batch_receive(&mut deps, NFT_CONTRACT, &["1", "2"], &transfer_msg_for("10")).unwrap();
+        assert_eq!(pack_of(&deps, "10"), Some("2".to_string()));
+        assert_eq!(pack_of(&deps, "11"), Some("1".to_string()));
+
+        // disbanding the first pack leaves the moved member with its new pack
+        batch_receive(&mut deps, NFT_CONTRACT, &["1"], &disband_msg(true)).unwrap();
+        assert_eq!(pack_of(&deps, "11"), None);
+        assert_eq!(pack_of(&deps, "10"), Some("2".to_string()));
+
+        // members stored before the index existed are picked up by a reindex
+        PACK_MEMBER_STORE.insert(&mut deps.storage, &"2".to_string(), &vec![member("10", 10), member("12", 30)]).unwrap();
+        assert_eq!(pack_of(&deps, "12"), None);
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), ExecuteMsg::ReindexPacks { start_page: 0, page_size: 10 }).unwrap();
+        assert_eq!(pack_of(&deps, "12"), Some("2".to_string()));
+    }
+}
@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Uint128};
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -11,8 +11,162 @@ pub enum ContractError {
     // issued when message sender != owner
     Unauthorized {},
 
-    #[error("Custom Error val: {val:?}")]
-    CustomError { val: String },
-    // Add any other custom errors you like here.
-    // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
+    // ------------------------------------------
+    //        Receive messages
+    // ------------------------------------------
+    #[error("No receive message was given")]
+    MissingReceiveMsg {},
+
+    #[error("Not a valid nft contract address: {address}")]
+    InvalidNftContract { address: String },
+
+    // ------------------------------------------
+    //        Payments and build credits
+    // ------------------------------------------
+    #[error("{address} is not a valid payment contract")]
+    UnknownPaymentContract { address: String },
+
+    #[error("Wrong payment amount, expected {expected} but received {received}")]
+    WrongPaymentAmount { expected: Uint128, received: Uint128 },
+
+    #[error("Quantity must be at least 1")]
+    InvalidQuantity {},
+
+    #[error("Payment not received")]
+    PaymentNotReceived {},
+
+    #[error("No unused build credits")]
+    NoBuildCredits {},
+
+    #[error("Payment name {name} already exists")]
+    PaymentNameExists { name: String },
+
+    #[error("Payment contract {address} already exists")]
+    PaymentContractExists { address: String },
+
+    #[error("Payment name {name} doesn't exist")]
+    UnknownPaymentName { name: String },
+
+    // ------------------------------------------
+    //        Pack building
+    // ------------------------------------------
+    #[error("Main token {token_id} is not in the list")]
+    MainTokenNotInList { token_id: String },
+
+    #[error("Token {token_id} is not in the list")]
+    TokenNotInList { token_id: String },
+
+    #[error("Token {token_id} is listed more than once")]
+    DuplicateToken { token_id: String },
+
+    #[error("Token {token_id} was not received by the contract")]
+    TokenNotReceived { token_id: String },
+
+    #[error("Pack of {main_token_id} is full, only {slots_left} slots left")]
+    PackFull { main_token_id: String, slots_left: u16 },
+
+    #[error("Rank pool doesn't have token {token_id}")]
+    RankNotFound { token_id: String },
+
+    #[error("Token {token_id} is an Alpha, you can't combine two Alphas")]
+    CombineAlphas { token_id: String },
+
+    #[error("Token {token_id} is not an Alpha")]
+    NotAlpha { token_id: String },
+
+    #[error("Wolf {token_id} is level {level}, level {required} is required to join a pack")]
+    LevelTooLow { token_id: String, level: u16, required: u16 },
+
+    #[error("Token {token_id} doesn't have a pack")]
+    NoPack { token_id: String },
+
+    #[error("Can't transfer a pack member of {token_id} to the same Alpha")]
+    SameAlpha { token_id: String },
+
+    #[error("Token {token_id} is not a member of the pack of {main_token_id}")]
+    NotPackMember { token_id: String, main_token_id: String },
+
+    #[error("Token {token_id} isn't part of this request")]
+    UnexpectedToken { token_id: String },
+
+    #[error("Pack name must be between {min} and {max} characters")]
+    InvalidPackNameLength { min: usize, max: usize },
+
+    #[error("Pack name can't contain {character:?}")]
+    InvalidPackNameChar { character: char },
+
+    #[error("Pack name isn't allowed")]
+    BlockedPackName {},
+
+    #[error("Pack name {name} is already taken")]
+    PackNameTaken { name: String },
+
+    #[error("Media of Alpha {token_id} doesn't match its pack members")]
+    MediaOutOfSync { token_id: String },
+
+    // ------------------------------------------
+    //        Nft metadata
+    // ------------------------------------------
+    #[error("Token {token_id} is missing {field}")]
+    MissingMetadata { token_id: String, field: String },
+
+    #[error("Token {token_id} is missing the {trait_type} trait")]
+    MissingTrait { token_id: String, trait_type: String },
+
+    #[error("Token {token_id} has an invalid {trait_type} value: {value:?}")]
+    InvalidTrait { token_id: String, trait_type: String, value: String },
+
+    #[error("Token {token_id} has no {metadata_type} media")]
+    MissingMedia { token_id: String, metadata_type: String },
+
+    // ------------------------------------------
+    //        Holding
+    // ------------------------------------------
+    #[error("No tokens in holding")]
+    NothingInHolding {},
+
+    #[error("Token {token_id} is not in holding")]
+    NotInHolding { token_id: String },
+
+    #[error("No token ids given")]
+    EmptyTokenList {},
+
+    // ------------------------------------------
+    //        Config
+    // ------------------------------------------
+    #[error("Pack max must be at least 1")]
+    InvalidPackMax {},
+
+    #[error("Level cap must be at least 1")]
+    InvalidLevelCap {},
+
+    #[error("Trait names can't be empty")]
+    EmptyTraitName {},
+
+    #[error("Trait name {name} is used more than once")]
+    DuplicateTraitName { name: String },
+
+    #[error("Tiered pack rank needs at least one tier")]
+    EmptyRankTiers {},
+
+    #[error("Rank tier {name} must cover higher ranks than the tier before it")]
+    RankTiersNotIncreasing { name: String },
+
+    #[error("At least one level is needed")]
+    EmptyLevels {},
+
+    #[error("Level {level} doesn't follow level {previous}")]
+    LevelNotContiguous { level: u16, previous: u16 },
+
+    #[error("Level {level} needs more xp than level {previous}")]
+    LevelXpNotIncreasing { level: u16, previous: u16 },
+
+    #[error("Level {level} isn't in the level table")]
+    UnknownLevel { level: u16 },
+
+    #[error("Only {max} ranks can be changed at a time")]
+    RankBatchTooLarge { max: u32 },
+
+    #[error("Rank {rank} of token {token_id} is outside of the collection size {collection_size}")]
+    RankOutOfRange { token_id: String, rank: u16, collection_size: u16 },
 }
@@ -20,16 +20,108 @@ pub struct InstantiateMsg {
     pub pack_max: u16,
     pub collection_size: u16,
     pub level_cap: u16,
+    pub member_requirement: Option<MemberRequirement>,
+    pub trait_names: Option<TraitNames>,
+    pub pack_rank_rule: Option<PackRankRule>,
     pub levels: Vec<Level>,
     pub ranks: Vec<Rank>
 } 
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct MigrateMsg {  
+    /// seeds viewing keys, which the old code didn't have
+    pub entropy: String,
+    /// builders whose history was written under their human address,
+    /// any left over can be moved with MigrateHistory
+    pub history_addresses: Vec<Addr>
+} 
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct Level {
     pub level: u16,
     pub xp_needed: u32
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MemberRequirement {
+    MinLevel {
+        level: u16
+    },
+    MinXp {
+        xp: u32
+    }
+}
+
+/// Trait types the contract reads from and writes to the nft metadata
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct TraitNames {
+    pub alpha: String,
+    pub xp: String,
+    pub level: String,
+    pub pack: String,
+    pub pack_rank: String
+}
+
+impl Default for TraitNames {
+    fn default() -> Self {
+        TraitNames {
+            alpha: "Alpha".to_string(),
+            xp: "XP".to_string(),
+            level: "LVL".to_string(),
+            pack: "Pack".to_string(),
+            pack_rank: "Pack Rank".to_string()
+        }
+    }
+}
+
+/// How much each pack member adds to the pack rank of its Alpha
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PackRankRule {
+    pub base: RankScore,
+    /// added on top of the base score for members that have the trait
+    pub trait_bonuses: Vec<TraitBonus>
+}
+
+impl Default for PackRankRule {
+    fn default() -> Self {
+        PackRankRule {
+            base: RankScore::LinearInverse,
+            trait_bonuses: vec![]
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RankScore {
+    /// collection size minus the member's rank
+    LinearInverse,
+    /// score of the first tier the member's rank fits in, 0 past the last tier
+    Tiered {
+        tiers: Vec<RankTier>
+    },
+    /// same score for every member
+    Flat {
+        value: u32
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct RankTier {
+    pub name: String,
+    /// highest (worst) rank that still falls in this tier
+    pub max_rank: u16,
+    pub score: u32
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct TraitBonus {
+    pub trait_type: String,
+    pub value: String,
+    pub bonus: u32
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct Rank {
     pub token_id: String,
@@ -51,19 +143,58 @@ pub struct PaymentContractInfo {
     /// contract's address
     pub address: Addr,
     pub payment_needed: Uint128,
+    /// charged for a rename when rename payments are on
+    pub rename_price: Uint128,
     pub name: String
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct BuildCredit {
+    /// snip20 contract the builds were paid with, none for builds paid before payments were
+    /// held by this contract, which were already sent on and can't be refunded
+    pub payment_contract: Option<ContractInfo>,
+    /// amount paid for each build
+    pub price: Uint128,
+    pub quantity: u32,
+    pub kind: CreditKind
+}
+
+/// What a credit was paid for, renames are priced apart from builds
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CreditKind {
+    Build,
+    Rename
+}
+
+impl Default for CreditKind {
+    fn default() -> Self {
+        CreditKind::Build
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct HistoryToken {
     pub wolf_main_token_id: String,
     pub pack_member_token_ids: Vec<String>, 
-    pub pack_build_date: Option<u64>
+    pub pack_build_date: Option<u64>,
+    pub kind: HistoryKind
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HistoryKind {
+    Build,
+    Transfer,
+    Disband,
+    Merge
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct ReceiveMsg {
-    pub quantity: u16
+    pub quantity: u16,
+    #[serde(default)]
+    pub kind: CreditKind
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
@@ -76,8 +207,30 @@ pub struct PackBuildMsg {
 pub struct PackTransferMsg {
     pub main_token_id: String,
     pub transfer_to_token_id: String,
-    pub token_id: String,
-    pub member_index: u8 
+    pub token_id: String
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PackDisbandMsg {
+    pub main_token_id: String,
+    /// keep the xp the members brought to the Alpha
+    pub keep_xp: bool
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PackRenameMsg {
+    pub main_token_id: String,
+    pub name: String
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PackMergeMsg {
+    pub main_token_id: String,
+    /// Alpha whose pack members move to the main Alpha
+    pub absorbed_token_id: String,
+    /// burn the absorbed Alpha and give all of its xp to the main Alpha,
+    /// otherwise it goes back as a solo Alpha keeping its own xp
+    pub burn_absorbed: bool
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
@@ -95,22 +248,80 @@ pub struct PackMain {
     pub name: String
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderboardOrder {
+    PackRank,
+    PackCount
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct LeaderboardEntry {
+    /// 1 based position on the leaderboard
+    pub position: u32,
+    pub pack: PackMain
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PackPositionResponse {
+    pub pack_rank_position: Option<u32>,
+    pub pack_count_position: Option<u32>
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct BuildInfoResponse {
     pub pack_max: u16,
+    pub member_requirement: MemberRequirement,
+    pub pack_rank_rule: PackRankRule,
+    pub is_rename_payment_needed: bool,
+    pub unique_pack_names: bool,
     pub total_burned: u16,
     pub valid_payments: Option<Vec<PaymentContractInfo>>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PackCapacityResponse {
+    pub pack_max: u16,
+    pub pack_count: u16,
+    pub slots_left: u16
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ViewingKeyResponse {
+    pub key: String
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct PackBuildPreviewResponse {
+    pub xp: Option<u32>,
+    pub level: Option<u16>,
+    pub pack_size: Option<u16>,
+    pub pack_rank: Option<u32>,
+    /// pack rank added by the new members
+    pub pack_rank_change: Option<u32>,
+    pub errors: Vec<String>,
+    /// checks a build makes that the preview can't, a build may still fail on these
+    pub unchecked: Vec<String>
+}
+
 #[derive(Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum HandleReceiveMsg {
     ReceivePackBuild {
         pack_build: PackBuildMsg
     },
-    // ReceiveTransferBuild {
-    //     transfer_build: PackTransferMsg
-    // },
+    ReceiveTransferBuild {
+        transfer_build: PackTransferMsg
+    },
+    ReceiveDisbandPack {
+        disband: PackDisbandMsg
+    },
+    ReceiveMergePacks {
+        merge: PackMergeMsg
+    },
+    ReceiveRenamePack {
+        rename: PackRenameMsg
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
@@ -119,6 +330,14 @@ pub enum ExecuteMsg {
     RevokePermit{
         permit_name: String
     },   
+    CreateViewingKey{
+        entropy: String,
+        padding: Option<String>
+    },
+    SetViewingKey{
+        key: String,
+        padding: Option<String>
+    },
     Receive{ 
         sender: Addr,
         from: Addr,
@@ -130,15 +349,73 @@ pub enum ExecuteMsg {
         token_ids: Vec<String>,
         msg: Option<Binary>
     },
-    // ClaimBack{  
-    // },
+    ClaimBack{  
+        token_ids: Option<Vec<String>>
+    },
+    AdminClaimBack{
+        owner: Addr,
+        token_ids: Option<Vec<String>>
+    },
+    RefundCredits{
+    },
+    AdminRefundCredits{
+        address: Addr
+    },
     SendNftBack{ 
         token_id: String,
         owner: Addr
     },
+    UpdateConfig{
+        receiving_address: Option<Addr>,
+        pack_max: Option<u16>,
+        collection_size: Option<u16>,
+        level_cap: Option<u16>,
+        is_payment_needed: Option<bool>,
+        nft_contract: Option<ContractInfo>,
+        member_requirement: Option<MemberRequirement>,
+        trait_names: Option<TraitNames>,
+        pack_rank_rule: Option<PackRankRule>,
+        is_rename_payment_needed: Option<bool>,
+        unique_pack_names: Option<bool>
+    },
+    SetNameBlocklist{
+        words: Vec<String>
+    },
+    RecomputePackRanks{
+        start_page: u32,
+        page_size: u32
+    },
+    SetLevels{
+        levels: Vec<Level>
+    },
+    AddLevels{
+        levels: Vec<Level>
+    },
+    RemoveLevels{
+        from_level: u16
+    },
+    SetRanks{
+        ranks: Vec<Rank>
+    },
+    ReindexPacks{
+        start_page: u32,
+        page_size: u32
+    },
+    MigrateHistory{
+        addresses: Vec<Addr>
+    },
+    RemoveRanks{
+        token_ids: Vec<String>
+    },
     AddPayment{ 
         payment: PaymentContractInfo
     },
+    UpdatePayment{
+        payment_name: String,
+        payment_needed: Option<Uint128>,
+        rename_price: Option<Uint128>,
+        code_hash: Option<String>
+    },
     RemovePayment{
         payment_name: String
     }
@@ -156,11 +433,47 @@ pub enum QueryMsg {
         start_page: u32, 
         page_size: u32 
     },
+    GetBuildCredits{
+        permit: Permit
+    },
+    GetNumUserHistoryWithKey{
+        address: Addr,
+        key: String
+    },
+    GetUserHistoryWithKey{
+        address: Addr,
+        key: String,
+        start_page: u32,
+        page_size: u32
+    },
+    GetBuildCreditsWithKey{
+        address: Addr,
+        key: String
+    },
+    GetLevels {},
+    GetXpForLevel{
+        level: u16
+    },
+    GetRank{
+        token_id: String
+    },
+    GetNumRanks {},
+    GetRanks{
+        start_page: u32,
+        page_size: u32
+    },
     GetNumPacks {},
     GetPacks {
         start_page: u32,
         page_size: u32 
     },
+    PreviewPackBuild{
+        main_token_id: String,
+        token_ids: Vec<String>
+    },
+    GetPackCapacity{
+        main_token_id: String
+    },
     GetPackMembers{
         main_token_id: String
     },
@@ -169,6 +482,20 @@ pub enum QueryMsg {
     },
     GetHolding{
         addr: Addr
+    },
+    GetLeaderboard{
+        start: u32,
+        limit: u32,
+        order_by: LeaderboardOrder
+    },
+    GetPackPosition{
+        main_token_id: String
+    },
+    GetPackByName{
+        name: String
+    },
+    GetPackOfMember{
+        token_id: String
     }
 } 
 
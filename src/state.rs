@@ -1,45 +1,102 @@
 use schemars::JsonSchema;
 use serde::{ Deserialize, Serialize};
 
-use cosmwasm_std::{CanonicalAddr, Addr}; 
+use cosmwasm_std::{CanonicalAddr, Addr, Uint128}; 
 use secret_toolkit::{ 
     storage:: { Item, Keymap, AppendStore }
 };
-use crate::msg::{HistoryToken, PaymentContractInfo, ContractInfo, Level, PackMain, PackMember};
+use crate::msg::{BuildCredit, MemberRequirement, TraitNames, PackRankRule, HistoryToken, PaymentContractInfo, ContractInfo, Level, PackMain, PackMember};
 
 pub static CONFIG_KEY: &[u8] = b"config"; 
 pub const ADMIN_KEY: &[u8] = b"admin";
-pub const MY_ADDRESS_KEY: &[u8] = b"my_address"; 
 pub const INHOLDING_NFT_KEY: &[u8] = b"inholding_nft";
 pub const PREFIX_REVOKED_PERMITS: &str = "revoke";
+pub const BUILD_CREDIT_KEY: &[u8] = b"build_credit";
 pub const PAID_KEY: &[u8] = b"paid";
 pub const HISTORY_KEY: &[u8] = b"history";
 pub const LEVEL_KEY: &[u8] = b"level";
 pub const RANK_KEY: &[u8] = b"rank";
 pub const PACK_KEY: &[u8] = b"pack";
 pub const PACK_MEMBER_KEY: &[u8] = b"pack_member";
+pub const PACK_RANK_INDEX_KEY: &[u8] = b"pack_rank_index";
+pub const PACK_COUNT_INDEX_KEY: &[u8] = b"pack_count_index";
+pub const PACK_RANK_SCORES_KEY: &[u8] = b"pack_rank_scores";
+pub const PACK_COUNT_SCORES_KEY: &[u8] = b"pack_count_scores";
+pub const NAME_BLOCKLIST_KEY: &[u8] = b"name_blocklist";
+pub const PACK_NAME_KEY: &[u8] = b"pack_name";
+pub const MEMBER_PACK_KEY: &[u8] = b"member_pack";
 
 pub static CONFIG_ITEM: Item<State> = Item::new(CONFIG_KEY); 
-pub static PAID_ADDRESSES_ITEM: Item<Vec<CanonicalAddr>> = Item::new(PAID_KEY);
+// config and paid builders as the code before build credits saved them, only read by migrate
+pub static LEGACY_CONFIG_ITEM: Item<LegacyState> = Item::new(CONFIG_KEY);
+pub static LEGACY_PAID_ADDRESSES_ITEM: Item<Vec<CanonicalAddr>> = Item::new(PAID_KEY);
 pub static ADMIN_ITEM: Item<CanonicalAddr> = Item::new(ADMIN_KEY); 
-pub static MY_ADDRESS_ITEM: Item<CanonicalAddr> = Item::new(MY_ADDRESS_KEY);   
 pub static HISTORY_STORE: AppendStore<HistoryToken> = AppendStore::new(HISTORY_KEY);
+// history entries written before they had a kind, only read by MigrateHistory
+pub static LEGACY_HISTORY_STORE: AppendStore<LegacyHistoryToken> = AppendStore::new(HISTORY_KEY);
 pub static LEVEL_ITEM: Item<Vec<Level>> = Item::new(LEVEL_KEY); 
 pub static RANK_STORE: Keymap<String, u16> = Keymap::new(RANK_KEY);
 pub static PACK_MAIN_STORE: Keymap<String, PackMain> = Keymap::new(PACK_KEY);
 pub static PACK_MEMBER_STORE: Keymap<String, Vec<PackMember>> = Keymap::new(PACK_MEMBER_KEY);
+// leaderboard indices, the distinct scores highest first and a store of main token ids for each
+// score, suffixed by the big endian score, so moving a pack only touches its old and new score
+pub static PACK_RANK_SCORES_ITEM: Item<Vec<u32>> = Item::new(PACK_RANK_SCORES_KEY);
+pub static PACK_RANK_INDEX_STORE: Keymap<String, ()> = Keymap::new(PACK_RANK_INDEX_KEY);
+pub static PACK_COUNT_SCORES_ITEM: Item<Vec<u32>> = Item::new(PACK_COUNT_SCORES_KEY);
+pub static PACK_COUNT_INDEX_STORE: Keymap<String, ()> = Keymap::new(PACK_COUNT_INDEX_KEY);
+// lowercase words pack names can't contain
+pub static NAME_BLOCKLIST_ITEM: Item<Vec<String>> = Item::new(NAME_BLOCKLIST_KEY);
+// lowercase pack name to main token id
+pub static PACK_NAME_STORE: Keymap<String, String> = Keymap::new(PACK_NAME_KEY);
+// pack member token id to the main token id of its pack
+pub static MEMBER_PACK_STORE: Keymap<String, String> = Keymap::new(MEMBER_PACK_KEY);
 pub static INHOLDING_NFT_STORE: Keymap<CanonicalAddr, Vec<String>> = Keymap::new(INHOLDING_NFT_KEY);
+pub static BUILD_CREDIT_STORE: Keymap<CanonicalAddr, Vec<BuildCredit>> = Keymap::new(BUILD_CREDIT_KEY);
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct State {  
     pub owner: Addr,   
     pub nft_contract: ContractInfo,
     pub is_payment_needed: bool,
+    pub is_rename_payment_needed: bool,
+    pub unique_pack_names: bool,
     pub valid_payments: Option<Vec<PaymentContractInfo>>,
     pub viewing_key: Option<String>,
     pub receiving_address: Addr,
     pub total_burned: u16,
     pub pack_max: u16,
     pub collection_size: u16,
+    pub level_cap: u16,
+    pub member_requirement: MemberRequirement,
+    pub trait_names: TraitNames,
+    pub pack_rank_rule: PackRankRule
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct LegacyHistoryToken {
+    pub wolf_main_token_id: String,
+    pub pack_member_token_ids: Vec<String>, 
+    pub pack_build_date: Option<u64>
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct LegacyState {  
+    pub owner: Addr,   
+    pub nft_contract: ContractInfo,
+    pub is_payment_needed: bool,
+    pub valid_payments: Option<Vec<LegacyPaymentContractInfo>>,
+    pub viewing_key: Option<String>,
+    pub receiving_address: Addr,
+    pub total_burned: u16,
+    pub pack_max: u16,
+    pub collection_size: u16,
     pub level_cap: u16
-} 
\ No newline at end of file
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct LegacyPaymentContractInfo {
+    pub code_hash: String,
+    pub address: Addr,
+    pub payment_needed: Uint128,
+    pub name: String
+}